@@ -0,0 +1,137 @@
+extern crate proc_macro;
+extern crate syn;
+#[macro_use]
+extern crate quote;
+
+
+use proc_macro::TokenStream;
+
+
+/// implements `ComponentBase`, the entity is kept in the field marked
+/// `#[entity]` or named `entity` and the manager type is named with
+/// `#[component_manager(Type)]` or `#[component_manager = "path::to::Type"]`,
+/// it is created with `Default::default`
+#[proc_macro_derive(Component, attributes(entity, component_manager))]
+pub fn derive_component(input: TokenStream) -> TokenStream {
+    let tokens = match syn::parse_derive_input(&input.to_string()) {
+        Ok(ast) => impl_component(&ast).unwrap_or_else(|error| compile_error(&error)),
+        Err(error) => compile_error(&error),
+    };
+    tokens.parse().unwrap()
+}
+
+/// implements `ComponentManagerBase`, the scene is kept in the field marked
+/// `#[scene]` or named `scene`
+#[proc_macro_derive(ComponentManager, attributes(scene))]
+pub fn derive_component_manager(input: TokenStream) -> TokenStream {
+    let tokens = match syn::parse_derive_input(&input.to_string()) {
+        Ok(ast) => impl_component_manager(&ast).unwrap_or_else(|error| compile_error(&error)),
+        Err(error) => compile_error(&error),
+    };
+    tokens.parse().unwrap()
+}
+
+fn impl_component(ast: &syn::DeriveInput) -> Result<quote::Tokens, String> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let entity = field(ast, "entity")?;
+    let component_manager = component_manager(ast)?;
+
+    Ok(quote! {
+        impl #impl_generics ::scene_graph::ComponentBase for #name #ty_generics #where_clause {
+            fn type_id(&self) -> ::std::any::TypeId {
+                ::std::any::TypeId::of::<#name #ty_generics>()
+            }
+
+            fn entity(&self) -> ::std::option::Option<::scene_graph::Entity> {
+                self.#entity.as_ref().and_then(|entity| entity.upgrade())
+            }
+            fn set_entity(&mut self, entity: ::std::option::Option<::scene_graph::WeakEntity>) {
+                self.#entity = entity;
+            }
+
+            fn new_component_manager(&self) -> ::std::boxed::Box<::scene_graph::ComponentManager> {
+                let component_manager: #component_manager = ::std::default::Default::default();
+                ::std::boxed::Box::new(component_manager)
+            }
+            fn component_manager_type_id(&self) -> ::std::any::TypeId {
+                ::std::any::TypeId::of::<#component_manager>()
+            }
+        }
+    })
+}
+
+fn impl_component_manager(ast: &syn::DeriveInput) -> Result<quote::Tokens, String> {
+    let name = &ast.ident;
+    let (impl_generics, ty_generics, where_clause) = ast.generics.split_for_impl();
+    let scene = field(ast, "scene")?;
+
+    Ok(quote! {
+        impl #impl_generics ::scene_graph::ComponentManagerBase for #name #ty_generics #where_clause {
+            fn type_id(&self) -> ::std::any::TypeId {
+                ::std::any::TypeId::of::<#name #ty_generics>()
+            }
+            fn name(&self) -> &'static str {
+                stringify!(#name)
+            }
+
+            fn scene(&self) -> ::std::option::Option<::scene_graph::Scene> {
+                self.#scene.as_ref().and_then(|scene| scene.upgrade())
+            }
+            fn set_scene(&mut self, scene: ::std::option::Option<::scene_graph::WeakScene>) {
+                self.#scene = scene;
+            }
+        }
+    })
+}
+
+fn compile_error(message: &str) -> quote::Tokens {
+    quote! {
+        compile_error!(#message);
+    }
+}
+
+fn field(ast: &syn::DeriveInput, name: &str) -> Result<syn::Ident, String> {
+    let fields = match ast.body {
+        syn::Body::Struct(syn::VariantData::Struct(ref fields)) => fields,
+        _ => return Err(format!("#[derive] for {} is only supported on structs with named fields", ast.ident)),
+    };
+
+    let marked = fields.iter().find(|field| {
+        field.attrs.iter().any(|attr| match attr.value {
+            syn::MetaItem::Word(ref ident) => ident == name,
+            _ => false,
+        })
+    });
+    let named = fields.iter().find(|field| {
+        field.ident.as_ref().map_or(false, |ident| ident == name)
+    });
+
+    match marked.or(named).and_then(|field| field.ident.clone()) {
+        Some(ident) => Ok(ident),
+        None => Err(format!("{} needs a field named `{}` or marked #[{}]", ast.ident, name, name)),
+    }
+}
+
+fn component_manager(ast: &syn::DeriveInput) -> Result<syn::Ty, String> {
+    for attr in ast.attrs.iter() {
+        if attr.name() != "component_manager" {
+            continue;
+        }
+
+        let ty: &str = match attr.value {
+            syn::MetaItem::List(_, ref items) => match items.first() {
+                Some(&syn::NestedMetaItem::MetaItem(syn::MetaItem::Word(ref ty))) if items.len() == 1 => ty.as_ref(),
+                _ => "",
+            },
+            syn::MetaItem::NameValue(_, syn::Lit::Str(ref ty, _)) => ty.as_str(),
+            _ => "",
+        };
+
+        return syn::parse_type(ty).map_err(|_| format!(
+            "{} needs #[component_manager(Type)] or #[component_manager = \"path::to::Type\"]",
+            ast.ident
+        ));
+    }
+    Err(format!("{} needs a #[component_manager(Type)] attribute", ast.ident))
+}
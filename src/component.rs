@@ -2,20 +2,46 @@ use alloc::boxed::Box;
 
 use core::any::{Any, TypeId};
 
-use entity::Entity;
+use entity::{Entity, WeakEntity};
+use entity_map::EntityMap;
+use scene::Scene;
 use component_manager::ComponentManager;
 
 
-pub trait Component: Any {
+/// the boilerplate half of a component, `#[derive(Component)]` from
+/// `scene_graph_derive` implements it
+pub trait ComponentBase: Any {
     fn type_id(&self) -> TypeId;
 
     fn entity(&self) -> Option<Entity>;
-    fn set_entity(&mut self, entity: Option<Entity>);
+    fn set_entity(&mut self, entity: Option<WeakEntity>);
 
     fn new_component_manager(&self) -> Box<ComponentManager>;
     fn component_manager_type_id(&self) -> TypeId;
 }
 
+pub trait Component: ComponentBase {
+    /// components that return true can be added to the same entity more than once
+    fn allows_multiple(&self) -> bool { false }
+
+    fn on_attach(&mut self) {}
+    fn on_detach(&mut self) {}
+    fn on_scene_enter(&mut self, _scene: &Scene) {}
+    fn on_scene_exit(&mut self, _scene: &Scene) {}
+
+    /// returns a copy for `Entity::instantiate`, which fails with
+    /// `SceneError::ComponentNotCloneable` for components that return `None`
+    fn clone_component(&self) -> Option<Box<Component>> { None }
+    /// called on copied components once the whole subtree is copied, so
+    /// references into the original subtree can be pointed at the copies
+    fn remap_entities(&mut self, _entity_map: &EntityMap) {}
+
+    /// fields a `Prefab` propagates to its instances unless overridden
+    fn prefab_fields(&self) -> &'static [&'static str] { &[] }
+    fn field_eq(&self, _field: &str, _other: &Component) -> bool { true }
+    fn copy_field(&mut self, _field: &str, _other: &Component) {}
+}
+
 impl Component {
     impl_any!();
 }
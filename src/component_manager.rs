@@ -1,19 +1,55 @@
 use alloc::boxed::Box;
+use alloc::vec::Vec;
 
 use core::any::{Any, TypeId};
 
-use scene::Scene;
+use scene::{Scene, WeakScene};
+use entity::Entity;
 use component::Component;
 
 
-pub trait ComponentManager: Any {
+/// the boilerplate half of a component manager, `#[derive(ComponentManager)]`
+/// from `scene_graph_derive` implements it
+pub trait ComponentManagerBase: Any {
     fn type_id(&self) -> TypeId;
+    /// the type's name, used in errors
+    fn name(&self) -> &'static str;
 
     fn scene(&self) -> Option<Scene>;
-    fn set_scene(&mut self, scene: Option<Scene>);
+    fn set_scene(&mut self, scene: Option<WeakScene>);
+}
 
+pub trait ComponentManager: ComponentManagerBase {
     fn is_empty(&self) -> bool;
 
+    /// managers with a lower order are initted and updated first once their
+    /// dependencies have run, managers with the same order run in the order
+    /// they were added
+    fn order(&self) -> isize { 0 }
+    /// type ids of the managers that must be initted and updated before this one
+    fn dependencies(&self) -> Vec<TypeId> { Vec::new() }
+
+    fn init(&mut self) {}
+    fn destroy(&mut self) {}
+    fn update(&mut self, _dt: f64) {}
+
+    /// called on the managers of an entity's components after its parent changed,
+    /// the new parent is `entity.parent()`, changes made while the manager is
+    /// locked, like from its own `update`, are delivered once it is unlocked
+    fn on_parent_changed(&mut self, _entity: &Entity, _prev_parent: Option<&Entity>) {}
+    fn on_child_added(&mut self, _entity: &Entity, _child: &Entity) {}
+    fn on_child_removed(&mut self, _entity: &Entity, _child: &Entity) {}
+    fn on_depth_changed(&mut self, _entity: &Entity, _depth: usize) {}
+
+    /// called on the managers of an entity's components when its
+    /// `active_in_hierarchy` changes, the components are removed from the
+    /// manager after `on_disable` and added back before `on_enable`
+    fn on_enable(&mut self, _entity: &Entity) {}
+    fn on_disable(&mut self, _entity: &Entity) {}
+
+    /// components added or removed while the manager is locked are queued like
+    /// the notifications above, a component taken off its entity then stays
+    /// attached until it is handed to `remove_component`
     fn add_component(&mut self, component: &mut Box<Component>);
     fn remove_component(&mut self, component: &mut Box<Component>);
 }
@@ -0,0 +1,83 @@
+use core::any::TypeId;
+use core::ops::{Deref, DerefMut};
+
+use entity::{self, Entity};
+use component::Component;
+
+
+/// a shared borrow of an entity's `T`, while it is alive the component can not
+/// be borrowed mutably, removed, taken or replaced
+pub struct ComponentRef<T: Component> {
+    entity: Entity,
+    component: *const T,
+}
+
+/// a mutable borrow of an entity's `T`, while it is alive the component can not
+/// be borrowed again, removed, taken or replaced, writing through it marks the
+/// component changed
+pub struct ComponentRefMut<T: Component> {
+    entity: Entity,
+    component: *mut T,
+}
+
+/// `entity` must have been marked as borrowing its `T`
+pub fn new_ref<T: Component>(entity: Entity, component: *const T) -> ComponentRef<T> {
+    ComponentRef {
+        entity: entity,
+        component: component,
+    }
+}
+/// `entity` must have been marked as borrowing its `T` mutably
+pub fn new_ref_mut<T: Component>(entity: Entity, component: *mut T) -> ComponentRefMut<T> {
+    ComponentRefMut {
+        entity: entity,
+        component: component,
+    }
+}
+
+impl<T: Component> ComponentRef<T> {
+    pub fn entity(&self) -> &Entity {
+        &self.entity
+    }
+}
+
+impl<T: Component> Deref for ComponentRef<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {&*self.component}
+    }
+}
+
+impl<T: Component> Drop for ComponentRef<T> {
+    fn drop(&mut self) {
+        entity::release_borrow(&self.entity, &TypeId::of::<T>());
+    }
+}
+
+impl<T: Component> ComponentRefMut<T> {
+    pub fn entity(&self) -> &Entity {
+        &self.entity
+    }
+}
+
+impl<T: Component> Deref for ComponentRefMut<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        unsafe {&*self.component}
+    }
+}
+
+impl<T: Component> DerefMut for ComponentRefMut<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        entity::mark_component_changed(&self.entity, &TypeId::of::<T>());
+        unsafe {&mut *self.component}
+    }
+}
+
+impl<T: Component> Drop for ComponentRefMut<T> {
+    fn drop(&mut self) {
+        entity::release_borrow(&self.entity, &TypeId::of::<T>());
+    }
+}
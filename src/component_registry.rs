@@ -0,0 +1,90 @@
+use alloc::boxed::Box;
+use alloc::string::String;
+use alloc::vec::Vec;
+
+use core::any::TypeId;
+
+use hash_map::HashMap;
+use insert::Insert;
+use map::Map;
+
+use registry_error::RegistryError;
+use component::Component;
+use component_manager::ComponentManager;
+
+
+struct Registration {
+    type_id: TypeId,
+    name: String,
+    constructor: Option<Box<Fn() -> Box<Component>>>,
+}
+
+pub struct ComponentRegistry {
+    registrations: Vec<Registration>,
+    by_type_id: HashMap<TypeId, usize>,
+    by_name: HashMap<String, usize>,
+}
+
+impl ComponentRegistry {
+    pub fn new() -> Self {
+        ComponentRegistry {
+            registrations: Vec::new(),
+            by_type_id: HashMap::new(),
+            by_name: HashMap::new(),
+        }
+    }
+
+    /// registers `T` under a name that stays the same across builds, unlike its `TypeId`,
+    /// each type and each name can only be registered once
+    pub fn register<T: Component>(&mut self, name: &str) -> Result<&mut Self, RegistryError> {
+        self.insert(TypeId::of::<T>(), name, None)
+    }
+    pub fn register_with<T: Component, F: 'static + Fn() -> T>(&mut self, name: &str, constructor: F) -> Result<&mut Self, RegistryError> {
+        self.insert(TypeId::of::<T>(), name, Some(Box::new(move || Box::new(constructor()) as Box<Component>)))
+    }
+    pub fn register_default<T: Component + Default>(&mut self, name: &str) -> Result<&mut Self, RegistryError> {
+        self.register_with::<T, _>(name, T::default)
+    }
+    pub fn register_component_manager<T: ComponentManager>(&mut self, name: &str) -> Result<&mut Self, RegistryError> {
+        self.insert(TypeId::of::<T>(), name, None)
+    }
+
+    pub fn is_registered(&self, name: &str) -> bool {
+        self.by_name.contains_key(&String::from(name))
+    }
+    pub fn type_name(&self, type_id: &TypeId) -> Option<&str> {
+        self.by_type_id.get(type_id).map(|&index| self.registrations[index].name.as_str())
+    }
+    pub fn type_id(&self, name: &str) -> Option<TypeId> {
+        self.by_name.get(&String::from(name)).map(|&index| self.registrations[index].type_id)
+    }
+
+    /// constructs the component registered as `name`, `None` if it is unknown
+    /// or was registered without a constructor
+    pub fn create(&self, name: &str) -> Option<Box<Component>> {
+        match self.by_name.get(&String::from(name)) {
+            Some(&index) => self.registrations[index].constructor.as_ref().map(|constructor| constructor()),
+            None => None,
+        }
+    }
+
+    fn insert(&mut self, type_id: TypeId, name: &str, constructor: Option<Box<Fn() -> Box<Component>>>) -> Result<&mut Self, RegistryError> {
+        if self.by_type_id.contains_key(&type_id) {
+            return Err(RegistryError::DuplicateType(type_id));
+        }
+        if self.is_registered(name) {
+            return Err(RegistryError::DuplicateName(String::from(name)));
+        }
+        let index = self.registrations.len();
+
+        self.registrations.push(Registration {
+            type_id: type_id,
+            name: String::from(name),
+            constructor: constructor,
+        });
+        self.by_type_id.insert(type_id, index);
+        self.by_name.insert(String::from(name), index);
+
+        Ok(self)
+    }
+}
@@ -0,0 +1,39 @@
+use alloc::vec::Vec;
+
+use core::any::TypeId;
+use core::fmt;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DependencyError {
+    MissingDependency {
+        component_manager: TypeId,
+        name: &'static str,
+        dependency: TypeId,
+    },
+    Cycle {
+        component_managers: Vec<TypeId>,
+        names: Vec<&'static str>,
+    },
+}
+
+impl fmt::Display for DependencyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &DependencyError::MissingDependency { ref name, .. } => write!(
+                f, "component manager {} depends on a component manager which is not in the scene", name
+            ),
+            &DependencyError::Cycle { ref names, .. } => {
+                write!(f, "component manager dependency cycle: ")?;
+
+                for (index, name) in names.iter().enumerate() {
+                    if index != 0 {
+                        write!(f, " -> ")?;
+                    }
+                    write!(f, "{}", name)?;
+                }
+                Ok(())
+            },
+        }
+    }
+}
@@ -1,60 +1,304 @@
 use alloc::boxed::Box;
+use alloc::arc::Arc;
+use alloc::string::String;
+use alloc::vec::Vec;
 
 use core::any::TypeId;
+use core::cmp;
+use core::mem;
+
+use spin::RwLock;
 
 use hash_map::HashMap;
 use insert::Insert;
 use map::Map;
+use iterable::Iterable;
 use iterable_mut::IterableMut;
 
 use vector::Vector;
 use stack::Stack;
 use remove::Remove;
 
-use shared::Shared;
+use handle::{Handle, WeakHandle};
 
-use scene::{self, Scene};
-use component::Component;
+use entity_id::EntityId;
+use hierarchy_error::HierarchyError;
+use scene_error::SceneError;
+use entity_map::{self, EntityMap};
+use entity_iter::{self, Children, Siblings, Ancestors, Descendants, DescendantsBreadthFirst};
+use scene::{self, Scene, WeakScene};
+use component::{Component, ComponentBase};
+use component_ref::{self, ComponentRef, ComponentRefMut};
+use component_manager::ComponentManager;
+use notification::Notification;
 
 
 struct EntityInner {
+    id: Option<EntityId>,
+    name: String,
+    tags: Vector<String>,
+    layer_mask: u32,
+    active: bool,
+    active_in_hierarchy: bool,
     depth: usize,
-    scene: Option<Scene>,
-    parent: Option<Entity>,
+    scene: Option<WeakScene>,
+    parent: Option<WeakEntity>,
     children: Vector<Entity>,
-    components: HashMap<TypeId, Box<Component>>,
+    components: HashMap<TypeId, Vector<Box<Component>>>,
+    registered: Vector<usize>,
+    changed: Vector<(TypeId, usize)>,
+    borrows: Vector<(TypeId, isize)>,
 }
 
+pub const DEFAULT_LAYER_MASK: u32 = 1u32;
+
 #[derive(Clone)]
 pub struct Entity {
-    inner: Shared<EntityInner>,
+    inner: Handle<EntityInner>,
+}
+
+#[derive(Clone)]
+pub struct WeakEntity {
+    inner: WeakHandle<EntityInner>,
 }
 
 impl Entity {
     pub fn new() -> Self {
         Entity {
-            inner: unsafe {
-                Shared::new(Box::into_raw(Box::new(EntityInner {
-                    depth: 0usize,
-                    scene: None,
-                    parent: None,
-                    children: Vector::new(),
-                    components: HashMap::new(),
-                })))
-            }
+            inner: Handle::new(EntityInner {
+                id: None,
+                name: String::new(),
+                tags: Vector::new(),
+                layer_mask: DEFAULT_LAYER_MASK,
+                active: true,
+                active_in_hierarchy: true,
+                depth: 0usize,
+                scene: None,
+                parent: None,
+                children: Vector::new(),
+                components: HashMap::new(),
+                registered: Vector::new(),
+                changed: Vector::new(),
+                borrows: Vector::new(),
+            }),
         }
     }
 
+    pub fn downgrade(&self) -> WeakEntity {
+        WeakEntity {
+            inner: self.inner.downgrade(),
+        }
+    }
+
+    /// panics while any of the entity's components are borrowed
     pub fn clear(&mut self) -> &mut Self {
         if let Some(inner) = unsafe {self.inner.as_mut()} {
+            if let Some(&(ref id, _)) = inner.borrows.iter().next() {
+                panic!("component {:?} is borrowed", id);
+            }
+            inner.id = None;
+            inner.name.clear();
+            inner.tags.clear();
+            inner.layer_mask = DEFAULT_LAYER_MASK;
+            inner.active = true;
+            inner.active_in_hierarchy = true;
             inner.depth = 0usize;
             inner.scene = None;
             inner.parent = None;
             inner.children.clear();
             inner.components.clear();
+            inner.registered.clear();
+            inner.changed.clear();
+        }
+        self
+    }
+
+    pub fn id(&self) -> Option<EntityId> {
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            inner.id
+        } else {
+            None
+        }
+    }
+
+    pub fn name(&self) -> &str {
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            &inner.name
+        } else {
+            ""
+        }
+    }
+    pub fn set_name<S: Into<String>>(&mut self, name: S) -> &mut Self {
+        if let Some(inner) = unsafe {self.inner.as_mut()} {
+            inner.name = name.into();
+        }
+        self
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            inner.tags.iter().any(|t| t == tag)
+        } else {
+            false
+        }
+    }
+    pub fn add_tag(&mut self, tag: &str) -> &mut Self {
+        let _ = self.try_add_tag(tag);
+        self
+    }
+    pub fn try_add_tag(&mut self, tag: &str) -> Result<&mut Self, SceneError> {
+        if self.has_tag(tag) {
+            return Err(SceneError::TagAlreadyAdded(String::from(tag)));
+        }
+        if let Some(inner) = unsafe {self.inner.as_mut()} {
+            inner.tags.push(String::from(tag));
+        }
+        let entity = self.clone();
+
+        if let Some(mut scene) = self.scene() {
+            scene::add_tag(&mut scene, &entity, tag);
+        }
+        Ok(self)
+    }
+    pub fn remove_tag(&mut self, tag: &str) -> &mut Self {
+        let _ = self.try_remove_tag(tag);
+        self
+    }
+    pub fn try_remove_tag(&mut self, tag: &str) -> Result<&mut Self, SceneError> {
+        let index = match unsafe {self.inner.as_ref()} {
+            Some(inner) => inner.tags.iter().position(|t| t == tag),
+            None => None,
+        };
+
+        match index {
+            Some(index) => {
+                if let Some(inner) = unsafe {self.inner.as_mut()} {
+                    inner.tags.remove(&index);
+                }
+                let entity = self.clone();
+
+                if let Some(mut scene) = self.scene() {
+                    scene::remove_tag(&mut scene, &entity, tag);
+                }
+                Ok(self)
+            },
+            None => Err(SceneError::TagNotFound(String::from(tag))),
+        }
+    }
+
+    pub fn layer_mask(&self) -> u32 {
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            inner.layer_mask
+        } else {
+            0u32
+        }
+    }
+    pub fn set_layer_mask(&mut self, layer_mask: u32) -> &mut Self {
+        if let Some(inner) = unsafe {self.inner.as_mut()} {
+            let entity = self.clone();
+            let prev_layer_mask = inner.layer_mask;
+
+            if let Some(mut scene) = self.scene() {
+                scene::remove_layers(&mut scene, &entity, prev_layer_mask);
+            }
+
+            inner.layer_mask = layer_mask;
+
+            if let Some(mut scene) = self.scene() {
+                scene::add_layers(&mut scene, &entity, layer_mask);
+            }
         }
         self
     }
+    pub fn in_layers(&self, layer_mask: u32) -> bool {
+        (self.layer_mask() & layer_mask) != 0u32
+    }
+
+    pub fn active_self(&self) -> bool {
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            inner.active
+        } else {
+            false
+        }
+    }
+    pub fn active_in_hierarchy(&self) -> bool {
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            inner.active_in_hierarchy
+        } else {
+            false
+        }
+    }
+    pub fn set_active(&mut self, active: bool) -> &mut Self {
+        if let Some(inner) = unsafe {self.inner.as_mut()} {
+            inner.active = active;
+        }
+        self.update_active_in_hierarchy();
+        self
+    }
+
+    fn update_active_in_hierarchy(&mut self) {
+        let active_in_hierarchy = self.active_self() &&
+            self.parent().map_or(true, |parent| parent.active_in_hierarchy());
+
+        let changed = match unsafe {self.inner.as_mut()} {
+            Some(inner) if inner.active_in_hierarchy != active_in_hierarchy => {
+                inner.active_in_hierarchy = active_in_hierarchy;
+                true
+            },
+            _ => false,
+        };
+
+        if changed {
+            if active_in_hierarchy {
+                notify(self, || Notification::Enable(self.clone()));
+            } else {
+                notify(self, || Notification::Disable(self.clone()));
+            }
+
+            if let Some(inner) = unsafe {self.inner.as_mut()} {
+                for child in inner.children.iter_mut() {
+                    child.update_active_in_hierarchy();
+                }
+            }
+        }
+    }
+
+    pub fn path(&self) -> String {
+        let mut path = String::new();
+
+        if let Some(parent) = self.parent() {
+            path.push_str(&parent.path());
+        }
+        path.push('/');
+        path.push_str(self.name());
+        path
+    }
+
+    pub fn find_child(&self, name: &str) -> Option<Entity> {
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            inner.children.iter().find(|child| child.name() == name).map(|child| child.clone())
+        } else {
+            None
+        }
+    }
+    pub fn find_path(&self, path: &str) -> Option<Entity> {
+        let mut entity = self.clone();
+
+        for name in path.split('/') {
+            entity = match name {
+                "" | "." => entity,
+                ".." => match entity.parent() {
+                    Some(parent) => parent,
+                    None => return None,
+                },
+                name => match entity.find_child(name) {
+                    Some(child) => child,
+                    None => return None,
+                },
+            };
+        }
+        Some(entity)
+    }
 
     pub fn depth(&self) -> usize {
         if let Some(inner) = unsafe {self.inner.as_ref()} {
@@ -64,120 +308,329 @@ impl Entity {
         }
     }
 
-    pub fn parent(&self) -> Option<&Entity> {
+    pub fn parent(&self) -> Option<Entity> {
         match unsafe {self.inner.as_ref()} {
             Some(inner) => match inner.parent {
-                Some(ref parent) => Some(parent),
-                None => None,
-            },
-            None => None,
-        }
-    }
-    pub fn parent_mut(&mut self) -> Option<&mut Entity> {
-        match unsafe {self.inner.as_mut()} {
-            Some(inner) => match inner.parent {
-                Some(ref mut parent) => Some(parent),
+                Some(ref parent) => parent.upgrade(),
                 None => None,
             },
             None => None,
         }
     }
 
-    pub fn scene(&self) -> Option<&Scene> {
+    pub fn scene(&self) -> Option<Scene> {
         match unsafe {self.inner.as_ref()} {
             Some(inner) => match inner.scene {
-                Some(ref scene) => Some(scene),
+                Some(ref scene) => scene.upgrade(),
                 None => None,
             },
             None => None,
         }
     }
-    pub fn scene_mut(&mut self) -> Option<&mut Scene> {
-        match unsafe {self.inner.as_mut()} {
-            Some(inner) => match inner.scene {
-                Some(ref mut scene) => Some(scene),
-                None => None,
-            },
-            None => None,
+
+    pub fn root(&self) -> Entity {
+        match self.ancestors().last() {
+            Some(root) => root,
+            None => self.clone(),
         }
     }
+    pub fn is_ancestor_of(&self, entity: &Entity) -> bool {
+        self != entity && in_subtree(entity, self)
+    }
 
-    fn update_children_depth(&mut self) {
-        if let Some(inner) = unsafe {self.inner.as_mut()} {
-            let depth = inner.depth + 1;
+    pub fn child_count(&self) -> usize {
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            inner.children.len()
+        } else {
+            0usize
+        }
+    }
+    pub fn children(&self) -> Children {
+        entity_iter::children(Some(self.clone()))
+    }
+    pub fn siblings(&self) -> Siblings {
+        entity_iter::siblings(self)
+    }
+    pub fn ancestors(&self) -> Ancestors {
+        entity_iter::ancestors(self)
+    }
+    pub fn descendants(&self) -> Descendants {
+        entity_iter::descendants(self)
+    }
+    pub fn descendants_breadth_first(&self) -> DescendantsBreadthFirst {
+        entity_iter::descendants_breadth_first(self)
+    }
+
+    fn set_depth(&mut self, depth: usize) {
+        let changed = match unsafe {self.inner.as_mut()} {
+            Some(inner) if inner.depth != depth => {
+                inner.depth = depth;
+                true
+            },
+            _ => false,
+        };
 
-            for child in inner.children.iter_mut() {
-                if let Some(child_inner) = unsafe {child.inner.as_mut()} {
-                    child_inner.depth = depth;
+        if changed {
+            notify(self, || Notification::DepthChanged(self.clone(), depth));
+
+            if let Some(inner) = unsafe {self.inner.as_mut()} {
+                for child in inner.children.iter_mut() {
+                    child.set_depth(depth + 1);
                 }
-                child.update_children_depth();
             }
         }
     }
 
-    pub fn add_child(&mut self, mut entity: Entity) -> &mut Self {
-        assert!(self != &entity);
-
+    pub fn add_child(&mut self, entity: Entity) -> Result<&mut Self, SceneError> {
+        let index = self.child_count();
+        self.insert_child(index, entity)
+    }
+    pub fn insert_child(&mut self, index: usize, mut entity: Entity) -> Result<&mut Self, SceneError> {
+        if entity.parent().as_ref() == Some(&*self) {
+            Err(SceneError::Hierarchy(HierarchyError::AlreadyChild))
+        } else {
+            entity.reparent(Some(&*self), index)?;
+            Ok(self)
+        }
+    }
+    pub fn swap_children(&mut self, a: usize, b: usize) -> &mut Self {
+        let _ = self.try_swap_children(a, b);
+        self
+    }
+    pub fn try_swap_children(&mut self, a: usize, b: usize) -> Result<&mut Self, SceneError> {
         if let Some(inner) = unsafe {self.inner.as_mut()} {
-            if let Some(child_inner) = unsafe {entity.inner.as_mut()} {
-                child_inner.parent = Some(self.clone());
-                child_inner.depth = inner.depth + 1;
-            }
-
-            entity.update_children_depth();
-            inner.children.push(entity.clone());
+            let len = inner.children.len();
 
-            if let Some(scene) = self.scene_mut() {
-                scene.add_entity(entity);
+            if a >= len {
+                return Err(SceneError::IndexOutOfBounds(a));
             }
+            if b >= len {
+                return Err(SceneError::IndexOutOfBounds(b));
+            }
+            if a != b {
+                let entity = inner.children[a].clone();
+                inner.children[a] = inner.children[b].clone();
+                inner.children[b] = entity;
+            }
+        }
+        Ok(self)
+    }
+
+    pub fn sibling_index(&self) -> Option<usize> {
+        match self.parent() {
+            Some(parent) => match unsafe {parent.inner.as_ref()} {
+                Some(parent_inner) => parent_inner.children.iter().position(|e| e == self),
+                None => None,
+            },
+            None => None,
+        }
+    }
+    pub fn set_sibling_index(&mut self, index: usize) -> &mut Self {
+        if let Some(parent) = self.parent() {
+            let index = cmp::min(index, parent.child_count() - 1usize);
+            let _ = self.try_set_sibling_index(index);
         }
         self
     }
-    pub fn remove_child(&mut self, entity: &mut Entity) -> &mut Self {
-        assert!(self != entity);
+    pub fn try_set_sibling_index(&mut self, index: usize) -> Result<&mut Self, SceneError> {
+        let parent = match self.parent() {
+            Some(parent) => parent,
+            None => return Err(SceneError::Hierarchy(HierarchyError::NotAChild)),
+        };
+        if let Some(parent_inner) = unsafe {parent.inner.as_mut()} {
+            if index >= parent_inner.children.len() {
+                return Err(SceneError::IndexOutOfBounds(index));
+            }
+            if let Some(current) = parent_inner.children.iter().position(|e| e == self) {
+                parent_inner.children.remove(&current);
+                parent_inner.children.insert(index, self.clone());
+            }
+        }
+        Ok(self)
+    }
+    pub fn move_before(&mut self, other: &Entity) -> Result<&mut Self, SceneError> {
+        self.move_next_to(other, 0usize)
+    }
+    pub fn move_after(&mut self, other: &Entity) -> Result<&mut Self, SceneError> {
+        self.move_next_to(other, 1usize)
+    }
+    fn move_next_to(&mut self, other: &Entity, offset: usize) -> Result<&mut Self, SceneError> {
+        if self == other {
+            return Ok(self);
+        }
+        match other.parent() {
+            Some(mut parent) => {
+                if self.parent().as_ref() == Some(&parent) {
+                    if let Some(parent_inner) = unsafe {parent.inner.as_mut()} {
+                        if let Some(current) = parent_inner.children.iter().position(|e| e == self) {
+                            parent_inner.children.remove(&current);
+                        }
+                        if let Some(index) = parent_inner.children.iter().position(|e| e == other) {
+                            parent_inner.children.insert(index + offset, self.clone());
+                        }
+                    }
+                } else if let Some(index) = other.sibling_index() {
+                    parent.insert_child(index + offset, self.clone())?;
+                }
+                Ok(self)
+            },
+            None => Err(SceneError::Hierarchy(HierarchyError::NotAChild)),
+        }
+    }
+    pub fn set_parent(&mut self, parent: Option<&Entity>) -> Result<&mut Self, SceneError> {
+        if self.parent().as_ref() == parent {
+            return Ok(self);
+        }
+        let index = parent.map_or(0usize, |parent| parent.child_count());
+        self.reparent(parent, index)?;
+        Ok(self)
+    }
+    fn reparent(&mut self, parent: Option<&Entity>, index: usize) -> Result<(), SceneError> {
+        if let Some(parent) = parent {
+            if in_subtree(parent, self) {
+                return Err(SceneError::Hierarchy(HierarchyError::WouldCreateCycle));
+            }
+        }
 
-        entity.detach();
+        let prev_scene = self.scene();
+        let next_scene = match parent {
+            Some(parent) => parent.scene(),
+            None => prev_scene.clone(),
+        };
+        let scene_changed = prev_scene != next_scene;
 
-        if let Some(scene) = self.scene_mut() {
-            scene.remove_entity(entity);
+        if scene_changed {
+            if let Some(mut scene) = prev_scene {
+                scene::remove_entity(&mut scene, self)?;
+            }
         }
-        self
+
+        let prev_parent = self.unlink();
+
+        if let Some(parent) = parent {
+            if let Some(parent_inner) = unsafe {parent.inner.as_mut()} {
+                if let Some(inner) = unsafe {self.inner.as_mut()} {
+                    inner.parent = Some(parent.downgrade());
+                }
+
+                let index = cmp::min(index, parent_inner.children.len());
+                parent_inner.children.insert(index, self.clone());
+            }
+        }
+
+        if scene_changed {
+            if let Some(mut scene) = next_scene {
+                scene::add_entity(&mut scene, self.clone())?;
+            }
+        }
+
+        self.set_depth(parent.map(|parent| parent.depth() + 1).unwrap_or(0usize));
+        self.update_active_in_hierarchy();
+        self.notify_parent_changed(prev_parent.as_ref(), parent);
+        Ok(())
     }
-    pub fn detach(&mut self) -> &mut Self {
+
+    fn unlink(&mut self) -> Option<Entity> {
         if let Some(inner) = unsafe {self.inner.as_mut()} {
-            if let Some(ref mut parent) = inner.parent {
+            let parent = inner.parent.take().and_then(|parent| parent.upgrade());
+
+            if let Some(ref parent) = parent {
                 if let Some(parent_inner) = unsafe {parent.inner.as_mut()} {
                     if let Some(index) = parent_inner.children.iter().position(|e| e == self) {
                         parent_inner.children.remove(&index);
                     }
                 }
             }
-            inner.depth = 0usize;
-            inner.parent = None;
+            parent
+        } else {
+            None
         }
-        self.update_children_depth();
-        self
     }
 
-    pub fn add_component<T: Component>(&mut self, mut component: T) -> &mut Self {
-        if let Some(inner) = unsafe {self.inner.as_mut()} {
-            let id = TypeId::of::<T>();
+    fn notify_parent_changed(&self, prev_parent: Option<&Entity>, parent: Option<&Entity>) {
+        if prev_parent == parent {
+            return;
+        }
+        if let Some(prev_parent) = prev_parent {
+            notify(prev_parent, || Notification::ChildRemoved(prev_parent.clone(), self.clone()));
+        }
+        if let Some(parent) = parent {
+            notify(parent, || Notification::ChildAdded(parent.clone(), self.clone()));
+        }
+        notify(self, || Notification::ParentChanged(self.clone(), prev_parent.cloned()));
+    }
 
-            if !inner.components.contains_key(&id) {
+    pub fn remove_child(&mut self, entity: &mut Entity) -> Result<&mut Self, SceneError> {
+        if entity.parent().as_ref() != Some(&*self) {
+            return Err(SceneError::Hierarchy(HierarchyError::NotAChild));
+        }
 
-                component.set_entity(Some(self.clone()));
+        entity.detach();
 
-                let mut component = Box::new(component) as Box<Component>;
+        if let Some(mut scene) = self.scene() {
+            scene::remove_entity(&mut scene, entity)?;
+        }
+        Ok(self)
+    }
+    pub fn try_detach(&mut self) -> Result<&mut Self, SceneError> {
+        if self.parent().is_none() {
+            Err(SceneError::Hierarchy(HierarchyError::NotAChild))
+        } else {
+            Ok(self.detach())
+        }
+    }
+    pub fn detach(&mut self) -> &mut Self {
+        let prev_parent = self.unlink();
+        self.set_depth(0usize);
+        self.update_active_in_hierarchy();
+        self.notify_parent_changed(prev_parent.as_ref(), None);
+        self
+    }
 
-                if let Some(scene) = self.scene_mut() {
-                    scene::add_component(scene, &mut component);
-                }
+    pub fn instantiate(&self, scene: Option<&mut Scene>) -> Result<Entity, SceneError> {
+        Ok(instantiate(self, scene)?.0)
+    }
 
-                inner.components.insert(id, component);
+    pub fn destroy(mut self) -> Result<(), SceneError> {
+        if let Some(mut scene) = self.scene() {
+            scene::remove_entity(&mut scene, &mut self)?;
+        }
+
+        let mut children = Vector::new();
+        let mut component_type_ids = Vector::new();
+
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            for child in inner.children.iter() {
+                children.push(child.clone());
+            }
+            for (id, _) in inner.components.iter() {
+                component_type_ids.push(*id);
             }
         }
+
+        for child in children.iter() {
+            child.clone().destroy()?;
+        }
+        for id in component_type_ids.iter() {
+            self.try_remove_component_by_type_id(id)?;
+        }
+
+        self.detach();
+        Ok(())
+    }
+
+    pub fn add_component<T: Component>(&mut self, component: T) -> &mut Self {
+        let _ = self.try_add_component(component);
         self
     }
+    pub fn try_add_component<T: Component>(&mut self, component: T) -> Result<&mut Self, SceneError> {
+        add_boxed_component(self, Box::new(component))?;
+        Ok(self)
+    }
+    pub fn try_add_boxed_component(&mut self, component: Box<Component>) -> Result<&mut Self, SceneError> {
+        add_boxed_component(self, component)?;
+        Ok(self)
+    }
     pub fn has_component<T: Component>(&self) -> bool {
         if let Some(inner) = unsafe {self.inner.as_ref()} {
             inner.components.contains_key(&TypeId::of::<T>())
@@ -188,87 +641,698 @@ impl Entity {
     pub fn remove_component<T: Component>(&mut self) -> &mut Self {
         self.remove_component_by_type_id(&TypeId::of::<T>())
     }
+    pub fn try_remove_component<T: Component>(&mut self) -> Result<&mut Self, SceneError> {
+        self.try_remove_component_by_type_id(&TypeId::of::<T>())
+    }
     pub fn remove_component_by_type_id(&mut self, id: &TypeId) -> &mut Self {
-        if let Some(inner) = unsafe {self.inner.as_mut()} {
-            let contains_key = inner.components.contains_key(&id);
+        let _ = self.try_remove_component_by_type_id(id);
+        self
+    }
+    pub fn try_remove_component_by_type_id(&mut self, id: &TypeId) -> Result<&mut Self, SceneError> {
+        let scene = self.scene();
 
-            if contains_key {
-                {
-                    let component = inner.components.get_mut(&id).unwrap();
+        check_not_borrowed(self, id)?;
 
-                    if let Some(scene) = self.scene_mut() {
-                        scene::remove_component(scene, component);
+        if let Some(inner) = unsafe {self.inner.as_mut()} {
+            let mut components = match inner.components.get_mut(id) {
+                Some(components) => {
+                    if let Some(ref scene) = scene {
+                        check_component_manager(scene, &components[0])?;
                     }
+                    mem::replace(components, Vector::new())
+                },
+                None => return Err(SceneError::ComponentNotFound(*id)),
+            };
+            remove_components_entry(inner, id);
 
-                    component.set_entity(None);
+            while components.len() != 0usize {
+                if let Some(component) = components.remove(&0usize) {
+                    remove_boxed_component(scene.clone(), component)?;
                 }
-                inner.components.remove(&id);
             }
         }
+        Ok(self)
+    }
+    pub fn remove_component_at<T: Component>(&mut self, index: usize) -> &mut Self {
+        let _ = self.try_remove_component_at::<T>(index);
         self
     }
+    pub fn try_remove_component_at<T: Component>(&mut self, index: usize) -> Result<&mut Self, SceneError> {
+        take_boxed_component(self, &TypeId::of::<T>(), index)?;
+        Ok(self)
+    }
+
+    pub fn insert_component<T: Component>(&mut self, component: T) -> Option<T> {
+        self.try_insert_component(component).unwrap_or(None)
+    }
+    pub fn try_insert_component<T: Component>(&mut self, component: T) -> Result<Option<T>, SceneError> {
+        if self.has_component::<T>() {
+            check_swap_component(self, &TypeId::of::<T>(), 0usize)?;
+            Ok(Some(swap_component(self, component)))
+        } else {
+            add_boxed_component(self, Box::new(component))?;
+            Ok(None)
+        }
+    }
+    pub fn replace_component<T: Component>(&mut self, component: T) -> Result<T, T> {
+        match check_swap_component(self, &TypeId::of::<T>(), 0usize) {
+            Ok(()) => Ok(swap_component(self, component)),
+            Err(_) => Err(component),
+        }
+    }
+    pub fn take_component<T: Component>(&mut self) -> Option<T> {
+        self.try_take_component::<T>().ok()
+    }
+    pub fn try_take_component<T: Component>(&mut self) -> Result<T, SceneError> {
+        let id = TypeId::of::<T>();
+
+        check_component_unlocked(self, &id, 0usize)?;
+
+        match take_boxed_component(self, &id, 0usize)?.map(downcast_boxed_component::<T>) {
+            Some(Ok(component)) => Ok(component),
+            _ => Err(SceneError::ComponentNotFound(id)),
+        }
+    }
+    /// panics while a query holds the component mutably
     pub fn component<T: Component>(&self) -> Option<&T> {
-        if let Some(inner) = unsafe {self.inner.as_mut()} {
-            let id = TypeId::of::<T>();
+        self.component_at::<T>(0usize)
+    }
+    /// panics while a query holds the component
+    pub fn component_mut<T: Component>(&self) -> Option<&mut T> {
+        self.component_at_mut::<T>(0usize)
+    }
+    pub fn component_at<T: Component>(&self, index: usize) -> Option<&T> {
+        check_borrow(self, &TypeId::of::<T>(), false);
+        component_at::<T>(self, index)
+    }
+    pub fn component_at_mut<T: Component>(&self, index: usize) -> Option<&mut T> {
+        check_borrow(self, &TypeId::of::<T>(), true);
+        component_at_mut::<T>(self, index)
+    }
+    pub fn components<T: Component>(&self) -> Vec<&T> {
+        let mut components = Vec::new();
+        let mut index = 0usize;
+
+        check_borrow(self, &TypeId::of::<T>(), false);
+
+        while let Some(component) = component_at::<T>(self, index) {
+            components.push(component);
+            index += 1usize;
+        }
+        components
+    }
+    pub fn component_count<T: Component>(&self) -> usize {
+        component_count(self, &TypeId::of::<T>())
+    }
+    /// whether `T` changed since the manager that is updating last ran, or
+    /// outside of updates since the end of the last `Scene::update`
+    pub fn is_changed<T: Component>(&self) -> bool {
+        match (unsafe {self.inner.as_ref()}, self.scene()) {
+            (Some(inner), Some(scene)) => {
+                let id = TypeId::of::<T>();
+                let last_change_tick = scene::last_change_tick(&scene);
+
+                inner.changed.iter().any(|&(ref changed, tick)| changed == &id && tick > last_change_tick)
+            },
+            _ => false,
+        }
+    }
+}
+
+pub fn component<'a, T: Component>(entity: &'a Entity) -> Option<&'a T> {
+    component_at::<T>(entity, 0usize)
+}
+pub fn component_mut<'a, T: Component>(entity: &'a Entity) -> Option<&'a mut T> {
+    component_at_mut::<T>(entity, 0usize)
+}
+pub fn component_at<'a, T: Component>(entity: &'a Entity, index: usize) -> Option<&'a T> {
+    match boxed_component(entity, &TypeId::of::<T>(), index) {
+        Some(c) => c.downcast_ref::<T>(),
+        None => None,
+    }
+}
+pub fn component_at_mut<'a, T: Component>(entity: &'a Entity, index: usize) -> Option<&'a mut T> {
+    match boxed_component_mut(entity, &TypeId::of::<T>(), index) {
+        Some(c) => c.downcast_mut::<T>(),
+        None => None,
+    }
+}
+
+/// -1 while a `ComponentRefMut` of `id` is alive, otherwise the number of `ComponentRef`s
+fn borrow_state(entity: &Entity, id: &TypeId) -> isize {
+    if let Some(inner) = unsafe {entity.inner.as_ref()} {
+        inner.borrows.iter().find(|&&(ref borrowed, _)| borrowed == id).map_or(0isize, |&(_, state)| state)
+    } else {
+        0isize
+    }
+}
 
-            if let Some(c) = inner.components.get(&id) {
-                c.downcast_ref::<T>()
+fn set_borrow_state(entity: &Entity, id: &TypeId, state: isize) {
+    if let Some(inner) = unsafe {entity.inner.as_mut()} {
+        match inner.borrows.iter().position(|&(ref borrowed, _)| borrowed == id) {
+            Some(index) => if state == 0isize {
+                inner.borrows.remove(&index);
             } else {
-                None
-            }
+                inner.borrows[index].1 = state;
+            },
+            None => if state != 0isize {
+                inner.borrows.push((*id, state));
+            },
+        }
+    }
+}
+
+fn is_borrowed(entity: &Entity, id: &TypeId) -> bool {
+    borrow_state(entity, id) != 0isize
+}
+
+fn check_not_borrowed(entity: &Entity, id: &TypeId) -> Result<(), SceneError> {
+    if is_borrowed(entity, id) {
+        Err(SceneError::ComponentBorrowed(*id))
+    } else {
+        Ok(())
+    }
+}
+
+fn check_borrow(entity: &Entity, id: &TypeId, mutable: bool) {
+    let state = borrow_state(entity, id);
+
+    if state < 0isize || (mutable && state != 0isize) {
+        panic!("component {:?} is already borrowed", id);
+    }
+}
+
+pub fn borrow_component<T: Component>(entity: &Entity) -> Option<ComponentRef<T>> {
+    let id = TypeId::of::<T>();
+
+    check_borrow(entity, &id, false);
+
+    match component::<T>(entity) {
+        Some(component) => {
+            let component = component as *const T;
+            set_borrow_state(entity, &id, borrow_state(entity, &id) + 1isize);
+            Some(component_ref::new_ref(entity.clone(), component))
+        },
+        None => None,
+    }
+}
+pub fn borrow_component_mut<T: Component>(entity: &Entity) -> Option<ComponentRefMut<T>> {
+    let id = TypeId::of::<T>();
+
+    check_borrow(entity, &id, true);
+
+    // the component is marked changed when it is written through the guard
+    let component = match unsafe {entity.inner.as_mut()} {
+        Some(inner) => match inner.components.get_mut(&id) {
+            Some(components) if components.len() != 0usize => components[0].downcast_mut::<T>().map(|component| component as *mut T),
+            _ => None,
+        },
+        None => None,
+    };
+
+    match component {
+        Some(component) => {
+            set_borrow_state(entity, &id, -1isize);
+            Some(component_ref::new_ref_mut(entity.clone(), component))
+        },
+        None => None,
+    }
+}
+pub fn release_borrow(entity: &Entity, id: &TypeId) {
+    match borrow_state(entity, id) {
+        state if state > 0isize => set_borrow_state(entity, id, state - 1isize),
+        _ => set_borrow_state(entity, id, 0isize),
+    }
+}
+
+pub fn component_type_ids(entity: &Entity) -> Vector<TypeId> {
+    let mut type_ids = Vector::new();
+
+    if let Some(inner) = unsafe {entity.inner.as_ref()} {
+        for (id, _) in inner.components.iter() {
+            type_ids.push(*id);
+        }
+    }
+    type_ids
+}
+
+pub fn component_count(entity: &Entity, id: &TypeId) -> usize {
+    if let Some(inner) = unsafe {entity.inner.as_ref()} {
+        inner.components.get(id).map_or(0usize, |components| components.len())
+    } else {
+        0usize
+    }
+}
+
+pub fn boxed_component<'a>(entity: &'a Entity, id: &TypeId, index: usize) -> Option<&'a Box<Component>> {
+    if let Some(inner) = unsafe {entity.inner.as_ref()} {
+        match inner.components.get(id) {
+            Some(components) if index < components.len() => Some(&components[index]),
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+pub fn boxed_component_mut<'a>(entity: &'a Entity, id: &TypeId, index: usize) -> Option<&'a mut Box<Component>> {
+    if let Some(inner) = unsafe {entity.inner.as_mut()} {
+        let exists = inner.components.get(id).map_or(false, |components| index < components.len());
+
+        if exists {
+            mark_changed(inner, *id);
+            inner.components.get_mut(id).map(|components| &mut components[index])
         } else {
             None
         }
+    } else {
+        None
     }
-    pub fn component_mut<T: Component>(&self) -> Option<&mut T> {
-        if let Some(inner) = unsafe {self.inner.as_mut()} {
-            let id = TypeId::of::<T>();
+}
+
+fn check_component_manager(scene: &Scene, component: &Box<Component>) -> Result<(), SceneError> {
+    let component_manager_type_id = component.component_manager_type_id();
+
+    if scene::has_component_manager(scene, &component_manager_type_id) {
+        Ok(())
+    } else {
+        Err(SceneError::ComponentManagerNotFound(component_manager_type_id))
+    }
+}
+
+/// hands a component taken off its entity back to its manager, when the manager
+/// is locked the component is queued with the removal and `None` is returned,
+/// it is detached once the manager has been told
+fn remove_boxed_component(scene: Option<Scene>, mut component: Box<Component>) -> Result<Option<Box<Component>>, SceneError> {
+    if let Some(mut scene) = scene {
+        component.on_scene_exit(&scene);
+
+        component = match scene::remove_boxed_component(&mut scene, component)? {
+            Some(component) => component,
+            None => return Ok(None),
+        };
+    }
+
+    component.on_detach();
+    component.set_entity(None);
+    Ok(Some(component))
+}
+
+fn take_boxed_component(entity: &mut Entity, id: &TypeId, index: usize) -> Result<Option<Box<Component>>, SceneError> {
+    let scene = entity.scene();
+
+    check_not_borrowed(entity, id)?;
+
+    if let Some(inner) = unsafe {entity.inner.as_mut()} {
+        let (component, is_empty) = match inner.components.get_mut(id) {
+            Some(components) => {
+                if index >= components.len() {
+                    return Err(SceneError::ComponentIndexOutOfBounds(*id, index));
+                }
+                if let Some(ref scene) = scene {
+                    check_component_manager(scene, &components[index])?;
+                }
+                let component = components.remove(&index);
+                (component, components.len() == 0usize)
+            },
+            None => return Err(SceneError::ComponentNotFound(*id)),
+        };
 
-            if let Some(c) = inner.components.get_mut(&id) {
-                c.downcast_mut::<T>()
+        if is_empty {
+            remove_components_entry(inner, id);
+        }
+        match component {
+            Some(component) => remove_boxed_component(scene, component),
+            None => Err(SceneError::ComponentNotFound(*id)),
+        }
+    } else {
+        Err(SceneError::ComponentNotFound(*id))
+    }
+}
+
+/// components handed back to the caller can not wait in the queue of a locked manager
+fn check_component_unlocked(entity: &Entity, id: &TypeId, index: usize) -> Result<(), SceneError> {
+    match (entity.scene(), boxed_component(entity, id, index)) {
+        (Some(scene), Some(component)) if is_registered(component) => {
+            let component_manager_type_id = component.component_manager_type_id();
+
+            if scene::is_component_manager_locked(&scene, &component_manager_type_id) {
+                Err(SceneError::ComponentManagerLocked(component_manager_type_id))
             } else {
-                None
+                Ok(())
             }
-        } else {
-            None
+        },
+        _ => Ok(()),
+    }
+}
+
+fn check_swap_component(entity: &Entity, id: &TypeId, index: usize) -> Result<(), SceneError> {
+    match component_count(entity, id) {
+        0 => Err(SceneError::ComponentNotFound(*id)),
+        count if index >= count => Err(SceneError::ComponentIndexOutOfBounds(*id, index)),
+        _ if is_borrowed(entity, id) => Err(SceneError::ComponentBorrowed(*id)),
+        _ => match (entity.scene(), boxed_component(entity, id, index)) {
+            (Some(scene), Some(component)) => {
+                check_component_manager(&scene, component)?;
+                check_component_unlocked(entity, id, index)
+            },
+            _ => Ok(()),
+        },
+    }
+}
+
+/// swaps the first `T` of `entity` for `component` without removing it from its
+/// manager's view of the entity, so the manager is never emptied and destroyed,
+/// `check_swap_component` must have passed
+fn swap_component<T: Component>(entity: &mut Entity, component: T) -> T {
+    let id = TypeId::of::<T>();
+    let scene = entity.scene();
+    let mut component: Box<Component> = Box::new(component);
+
+    if let Some(inner) = unsafe {entity.inner.as_mut()} {
+        if let Some(components) = inner.components.get_mut(&id) {
+            if let Some(ref scene) = scene {
+                components[0].on_scene_exit(scene);
+            }
+            component.set_entity(Some(entity.downgrade()));
+            component.on_attach();
+
+            if let Some(mut scene) = scene {
+                scene::swap_component(&mut scene, &mut components[0], &mut component);
+                component.on_scene_enter(&scene);
+            }
+
+            mem::swap(&mut components[0], &mut component);
+            component.on_detach();
+            component.set_entity(None);
         }
+        mark_changed(inner, id);
+    }
+
+    downcast_boxed_component::<T>(component).ok().expect("component stored under the TypeId of another type")
+}
+
+fn downcast_boxed_component<T: Component>(component: Box<Component>) -> Result<T, Box<Component>> {
+    if component.downcast_ref::<T>().is_some() {
+        Ok(*unsafe {Box::from_raw(Box::into_raw(component) as *mut T)})
+    } else {
+        Err(component)
     }
 }
 
-pub fn set_scene<'a>(entity: &'a mut Entity, scene: &'a mut Scene) {
+fn remove_components_entry(inner: &mut EntityInner, id: &TypeId) {
+    inner.components.remove(id);
+
+    if let Some(index) = inner.changed.iter().position(|&(ref changed, _)| changed == id) {
+        inner.changed.remove(&index);
+    }
+}
+
+/// stamps `id` with the scene's change tick, components of entities outside of a
+/// scene are stamped when the entity is added to one
+fn mark_changed(inner: &mut EntityInner, id: TypeId) {
+    let tick = match inner.scene.as_ref().and_then(|scene| scene.upgrade()) {
+        Some(scene) => scene::change_tick(&scene),
+        None => 0usize,
+    };
+    stamp_changed(&mut inner.changed, id, tick);
+}
+
+fn stamp_changed(changed: &mut Vector<(TypeId, usize)>, id: TypeId, tick: usize) {
+    match changed.iter().position(|&(ref changed, _)| changed == &id) {
+        Some(index) => {
+            changed[index].1 = tick;
+        },
+        None => {
+            changed.push((id, tick));
+        },
+    }
+}
+
+pub fn mark_component_changed(entity: &Entity, id: &TypeId) {
     if let Some(inner) = unsafe {entity.inner.as_mut()} {
-        inner.scene = Some(scene.clone());
+        mark_changed(inner, *id);
+    }
+}
+
+pub fn child_at(entity: &Entity, index: usize) -> Option<Entity> {
+    match unsafe {entity.inner.as_ref()} {
+        Some(inner) if index < inner.children.len() => Some(inner.children[index].clone()),
+        _ => None,
+    }
+}
+
+pub fn set_id(entity: &mut Entity, id: Option<EntityId>) {
+    if let Some(inner) = unsafe {entity.inner.as_mut()} {
+        inner.id = id;
+    }
+}
+
+pub fn tags(entity: &Entity) -> Option<&Vector<String>> {
+    unsafe {entity.inner.as_ref()}.map(|inner| &inner.tags)
+}
+
+pub fn in_subtree(entity: &Entity, root: &Entity) -> bool {
+    if entity == root {
+        true
+    } else {
+        match entity.parent() {
+            Some(parent) => in_subtree(&parent, root),
+            None => false,
+        }
+    }
+}
+
+pub fn set_scene<'a>(entity: &'a mut Entity, scene: &'a mut Scene) -> Result<(), SceneError> {
+    if let Some(inner) = unsafe {entity.inner.as_mut()} {
+        inner.scene = Some(scene.downgrade());
+
+        let tick = scene::change_tick(scene);
+
+        for (id, _) in inner.components.iter() {
+            stamp_changed(&mut inner.changed, *id, tick);
+        }
 
+        // children added to the scene on their own before their parent are already in it
         for child in inner.children.iter_mut() {
-            scene.add_entity(child.clone());
+            if child.scene().as_ref() != Some(&*scene) {
+                scene::add_entity(scene, child.clone())?;
+            }
         }
-        for (_, component) in inner.components.iter_mut() {
-            scene::add_component(scene, component);
+        let active = inner.active_in_hierarchy;
+
+        for (_, components) in inner.components.iter_mut() {
+            for component in components.iter_mut() {
+                scene::add_component(scene, component, active)?;
+                component.on_scene_enter(scene);
+            }
         }
     }
+    Ok(())
 }
-pub fn remove_scene<'a>(entity: &'a mut Entity, scene: &'a mut Scene) {
+pub fn add_boxed_component<'a>(entity: &'a mut Entity, mut component: Box<Component>) -> Result<(), SceneError> {
+    let id = ComponentBase::type_id(&*component);
+
     if let Some(inner) = unsafe {entity.inner.as_mut()} {
-        for (_, component) in inner.components.iter_mut() {
-            scene::remove_component(scene, component);
+        if inner.components.contains_key(&id) && !component.allows_multiple() {
+            return Err(SceneError::ComponentAlreadyAdded(id));
+        }
+
+        component.set_entity(Some(entity.downgrade()));
+        component.on_attach();
+
+        if let Some(mut scene) = entity.scene() {
+            if let Err(error) = scene::add_component(&mut scene, &mut component, inner.active_in_hierarchy) {
+                component.on_detach();
+                component.set_entity(None);
+                return Err(error);
+            }
+            component.on_scene_enter(&scene);
+        }
+
+        if !inner.components.contains_key(&id) {
+            inner.components.insert(id, Vector::new());
+        }
+        if let Some(components) = inner.components.get_mut(&id) {
+            components.push(component);
+        }
+        mark_changed(inner, id);
+    }
+    Ok(())
+}
+
+pub fn instantiate<'a>(entity: &'a Entity, scene: Option<&'a mut Scene>) -> Result<(Entity, EntityMap), SceneError> {
+    let mut entity_map = entity_map::new();
+    let mut components = Vec::new();
+    let copy = copy_subtree(entity, &mut entity_map, &mut components)?;
+
+    for &mut (_, ref mut component) in components.iter_mut() {
+        component.remap_entities(&entity_map);
+    }
+    for (mut entity, component) in components {
+        add_boxed_component(&mut entity, component)?;
+    }
+
+    if let Some(scene) = scene {
+        scene.add_entity(copy.clone())?;
+    }
+    Ok((copy, entity_map))
+}
+
+fn copy_subtree(entity: &Entity, entity_map: &mut EntityMap, components: &mut Vec<(Entity, Box<Component>)>) -> Result<Entity, SceneError> {
+    let mut copy = Entity::new();
+
+    if let Some(inner) = unsafe {entity.inner.as_ref()} {
+        copy.set_name(inner.name.as_str());
+        copy.set_layer_mask(inner.layer_mask);
+        copy.set_active(inner.active);
+
+        for tag in inner.tags.iter() {
+            copy.add_tag(tag);
+        }
+        for (_, entity_components) in inner.components.iter() {
+            for component in entity_components.iter() {
+                match component.clone_component() {
+                    Some(component) => components.push((copy.clone(), component)),
+                    None => return Err(SceneError::ComponentNotCloneable(ComponentBase::type_id(&**component))),
+                }
+            }
+        }
+
+        entity_map::insert(entity_map, entity.clone(), copy.clone());
+
+        for child in inner.children.iter() {
+            let child_copy = copy_subtree(child, entity_map, components)?;
+            copy.add_child(child_copy)?;
+        }
+    }
+    Ok(copy)
+}
+
+/// the address of a component's data, it identifies the component in queued notifications
+pub fn component_key(component: &Box<Component>) -> usize {
+    &**component as *const Component as *const u8 as usize
+}
+
+/// whether the manager of `component` holds it
+pub fn is_registered(component: &Box<Component>) -> bool {
+    let key = component_key(component);
+
+    match component.entity() {
+        Some(entity) => match unsafe {entity.inner.as_ref()} {
+            Some(inner) => inner.registered.iter().any(|k| *k == key),
+            None => false,
+        },
+        None => false,
+    }
+}
+/// hands `component` to `component_manager` unless it already holds it
+pub fn register_component(component_manager: &mut ComponentManager, component: &mut Box<Component>) {
+    if !is_registered(component) {
+        if let Some(entity) = component.entity() {
+            if let Some(inner) = unsafe {entity.inner.as_mut()} {
+                inner.registered.push(component_key(component));
+            }
+        }
+        component_manager.add_component(component);
+    }
+}
+/// takes `component` from `component_manager` if it holds it
+pub fn unregister_component(component_manager: &mut ComponentManager, component: &mut Box<Component>) {
+    if is_registered(component) {
+        let key = component_key(component);
+
+        if let Some(entity) = component.entity() {
+            if let Some(inner) = unsafe {entity.inner.as_mut()} {
+                if let Some(index) = inner.registered.iter().position(|k| *k == key) {
+                    inner.registered.remove(&index);
+                }
+            }
+        }
+        component_manager.remove_component(component);
+    }
+}
+
+pub fn each_component_mut<F: FnMut(&mut Box<Component>)>(entity: &Entity, component_manager_type_id: &TypeId, mut f: F) {
+    if let Some(inner) = unsafe {entity.inner.as_mut()} {
+        for (_, components) in inner.components.iter_mut() {
+            for component in components.iter_mut() {
+                if &component.component_manager_type_id() == component_manager_type_id {
+                    f(component);
+                }
+            }
+        }
+    }
+}
+
+fn component_managers(entity: &Entity) -> Vector<(TypeId, Arc<RwLock<Box<ComponentManager>>>)> {
+    let mut component_managers = Vector::new();
+
+    if let Some(scene) = entity.scene() {
+        if let Some(inner) = unsafe {entity.inner.as_ref()} {
+            let mut type_ids = Vector::new();
+
+            for (_, components) in inner.components.iter() {
+                let component_manager_type_id = components[0].component_manager_type_id();
+
+                if !type_ids.iter().any(|type_id| *type_id == component_manager_type_id) {
+                    if let Some(component_manager) = scene::component_manager(&scene, &component_manager_type_id) {
+                        component_managers.push((component_manager_type_id, component_manager));
+                    }
+                    type_ids.push(component_manager_type_id);
+                }
+            }
+        }
+    }
+    component_managers
+}
+
+fn notify<F: Fn() -> Notification>(entity: &Entity, notification: F) {
+    for &(ref component_manager_type_id, ref component_manager) in component_managers(entity).iter() {
+        match component_manager.try_write() {
+            Some(mut component_manager) => {
+                if let Some(scene) = entity.scene() {
+                    scene::flush_component_manager(&scene, component_manager_type_id, &mut **component_manager);
+                }
+                notification().deliver(&mut **component_manager);
+            },
+            None => if let Some(mut scene) = entity.scene() {
+                scene::defer_notification(&mut scene, *component_manager_type_id, notification());
+            },
+        }
+    }
+}
+
+pub fn remove_scene<'a>(entity: &'a mut Entity, scene: &'a mut Scene) -> Result<(), SceneError> {
+    if let Some(inner) = unsafe {entity.inner.as_mut()} {
+        for (_, components) in inner.components.iter_mut() {
+            for component in components.iter_mut() {
+                component.on_scene_exit(scene);
+                scene::remove_component(scene, component)?;
+            }
         }
         for child in inner.children.iter_mut() {
-            scene.remove_entity(child);
+            if child.scene().as_ref() == Some(&*scene) {
+                scene::remove_entity(scene, child)?;
+            }
         }
 
         inner.scene = None;
     }
+    Ok(())
+}
+
+impl WeakEntity {
+    pub fn upgrade(&self) -> Option<Entity> {
+        self.inner.upgrade().map(|inner| Entity {
+            inner: inner,
+        })
+    }
 }
 
 impl PartialEq<Entity> for Entity {
     fn eq(&self, other: &Self) -> bool {
-        match unsafe {self.inner.as_ref()} {
-            Some(a) => match unsafe {other.inner.as_ref()} {
-                Some(b) => a as *const _ == b as *const _,
-                None => false,
-            },
-            None => false,
-        }
+        self.inner.ptr_eq(&other.inner)
     }
     fn ne(&self, other: &Self) -> bool {
         !self.eq(other)
@@ -0,0 +1,30 @@
+use core::fmt;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct EntityId {
+    index: u32,
+    generation: u32,
+}
+
+impl EntityId {
+    pub fn new(index: u32, generation: u32) -> Self {
+        EntityId {
+            index: index,
+            generation: generation,
+        }
+    }
+
+    pub fn index(&self) -> u32 {
+        self.index
+    }
+    pub fn generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl fmt::Display for EntityId {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
@@ -0,0 +1,171 @@
+use alloc::vec::Vec;
+
+use entity::{self, Entity};
+
+
+pub struct Children {
+    parent: Option<Entity>,
+    index: usize,
+}
+
+pub fn children(parent: Option<Entity>) -> Children {
+    Children {
+        parent: parent,
+        index: 0usize,
+    }
+}
+
+impl Iterator for Children {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let child = match self.parent {
+            Some(ref parent) => entity::child_at(parent, self.index),
+            None => None,
+        };
+        self.index += 1;
+        child
+    }
+}
+
+
+pub struct Siblings {
+    entity: Entity,
+    children: Children,
+}
+
+pub fn siblings(entity: &Entity) -> Siblings {
+    Siblings {
+        entity: entity.clone(),
+        children: children(entity.parent()),
+    }
+}
+
+impl Iterator for Siblings {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(child) = self.children.next() {
+            if child != self.entity {
+                return Some(child);
+            }
+        }
+        None
+    }
+}
+
+
+pub struct Ancestors {
+    entity: Option<Entity>,
+}
+
+pub fn ancestors(entity: &Entity) -> Ancestors {
+    Ancestors {
+        entity: entity.parent(),
+    }
+}
+
+impl Iterator for Ancestors {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.entity.take() {
+            Some(entity) => {
+                self.entity = entity.parent();
+                Some(entity)
+            },
+            None => None,
+        }
+    }
+}
+
+
+/// pre-order depth first traversal, the stack only grows with the depth of
+/// the tree so stepping does not allocate once it reached that depth
+pub struct Descendants {
+    stack: Vec<(Entity, usize)>,
+}
+
+pub fn descendants(entity: &Entity) -> Descendants {
+    let mut stack = Vec::new();
+    stack.push((entity.clone(), 0usize));
+
+    Descendants {
+        stack: stack,
+    }
+}
+
+impl Iterator for Descendants {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let child = match self.stack.last_mut() {
+                Some(&mut (ref parent, ref mut index)) => {
+                    let child = entity::child_at(parent, *index);
+                    *index += 1;
+                    child
+                },
+                None => return None,
+            };
+
+            match child {
+                Some(child) => {
+                    self.stack.push((child.clone(), 0usize));
+                    return Some(child);
+                },
+                None => {
+                    self.stack.pop();
+                },
+            }
+        }
+    }
+}
+
+
+/// breadth first traversal, the queue is reused so stepping only allocates
+/// when a level is wider than any level seen before
+pub struct DescendantsBreadthFirst {
+    queue: Vec<Entity>,
+    head: usize,
+    index: usize,
+}
+
+pub fn descendants_breadth_first(entity: &Entity) -> DescendantsBreadthFirst {
+    let mut queue = Vec::new();
+    queue.push(entity.clone());
+
+    DescendantsBreadthFirst {
+        queue: queue,
+        head: 0usize,
+        index: 0usize,
+    }
+}
+
+impl Iterator for DescendantsBreadthFirst {
+    type Item = Entity;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.head < self.queue.len() {
+            let child = entity::child_at(&self.queue[self.head], self.index);
+
+            match child {
+                Some(child) => {
+                    self.index += 1;
+
+                    if self.head > 0usize && self.head * 2usize >= self.queue.len() {
+                        self.queue.drain(..self.head);
+                        self.head = 0usize;
+                    }
+                    self.queue.push(child.clone());
+                    return Some(child);
+                },
+                None => {
+                    self.head += 1;
+                    self.index = 0usize;
+                },
+            }
+        }
+        None
+    }
+}
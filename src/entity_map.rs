@@ -0,0 +1,53 @@
+use vector::Vector;
+use stack::Stack;
+use iterable::Iterable;
+
+use entity::{Entity, WeakEntity};
+
+
+pub struct EntityMap {
+    entities: Vector<(Entity, Entity)>,
+}
+
+impl EntityMap {
+    pub fn len(&self) -> usize {
+        self.entities.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.entities.len() == 0usize
+    }
+
+    pub fn get(&self, entity: &Entity) -> Option<Entity> {
+        self.entities.iter()
+            .find(|&&(ref from, _)| from == entity)
+            .map(|&(_, ref to)| to.clone())
+    }
+    pub fn get_weak(&self, entity: &WeakEntity) -> Option<WeakEntity> {
+        entity.upgrade()
+            .and_then(|entity| self.get(&entity))
+            .map(|entity| entity.downgrade())
+    }
+
+    /// returns the copy of `entity` if it was part of the copied subtree,
+    /// otherwise `entity` itself
+    pub fn remap(&self, entity: &Entity) -> Entity {
+        self.get(entity).unwrap_or_else(|| entity.clone())
+    }
+    pub fn remap_weak(&self, entity: &WeakEntity) -> WeakEntity {
+        self.get_weak(entity).unwrap_or_else(|| entity.clone())
+    }
+}
+
+pub fn new() -> EntityMap {
+    EntityMap {
+        entities: Vector::new(),
+    }
+}
+
+pub fn insert(entity_map: &mut EntityMap, from: Entity, to: Entity) {
+    entity_map.entities.push((from, to));
+}
+
+pub fn entities(entity_map: &EntityMap) -> &Vector<(Entity, Entity)> {
+    &entity_map.entities
+}
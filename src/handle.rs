@@ -0,0 +1,62 @@
+use alloc::rc::{Rc, Weak};
+
+use core::cell::UnsafeCell;
+
+
+pub struct Handle<T> {
+    inner: Rc<UnsafeCell<T>>,
+}
+
+impl<T> Handle<T> {
+    pub fn new(value: T) -> Self {
+        Handle {
+            inner: Rc::new(UnsafeCell::new(value)),
+        }
+    }
+
+    pub unsafe fn as_ref(&self) -> Option<&T> {
+        Some(&*self.inner.get())
+    }
+    pub unsafe fn as_mut(&self) -> Option<&mut T> {
+        Some(&mut *self.inner.get())
+    }
+
+    pub fn downgrade(&self) -> WeakHandle<T> {
+        WeakHandle {
+            inner: Rc::downgrade(&self.inner),
+        }
+    }
+
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.inner, &other.inner)
+    }
+}
+
+impl<T> Clone for Handle<T> {
+    fn clone(&self) -> Self {
+        Handle {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+
+pub struct WeakHandle<T> {
+    inner: Weak<UnsafeCell<T>>,
+}
+
+impl<T> WeakHandle<T> {
+    pub fn upgrade(&self) -> Option<Handle<T>> {
+        self.inner.upgrade().map(|inner| Handle {
+            inner: inner,
+        })
+    }
+}
+
+impl<T> Clone for WeakHandle<T> {
+    fn clone(&self) -> Self {
+        WeakHandle {
+            inner: self.inner.clone(),
+        }
+    }
+}
@@ -0,0 +1,21 @@
+use core::fmt;
+
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HierarchyError {
+    WouldCreateCycle,
+    AlreadyChild,
+    NotAChild,
+    DifferentScene,
+}
+
+impl fmt::Display for HierarchyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &HierarchyError::WouldCreateCycle => write!(f, "entity can not be a child of itself or of one of its descendants"),
+            &HierarchyError::AlreadyChild => write!(f, "entity is already a child of this parent"),
+            &HierarchyError::NotAChild => write!(f, "entity is not a child of this parent"),
+            &HierarchyError::DifferentScene => write!(f, "entity belongs to a different scene"),
+        }
+    }
+}
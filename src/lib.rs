@@ -22,16 +22,50 @@ extern crate vector;
 extern crate stack;
 extern crate remove;
 
-extern crate shared;
+#[cfg(feature = "serialize")]
+extern crate serde;
+#[cfg(feature = "serialize")]
+#[macro_use]
+extern crate serde_derive;
+#[cfg(feature = "serialize")]
+extern crate serde_value;
 
 
+mod dependency_error;
+mod hierarchy_error;
+mod registry_error;
+mod scene_error;
+mod handle;
 mod component_manager;
 mod component;
+mod component_ref;
+mod component_registry;
 mod scene;
+mod entity_id;
+mod entity_iter;
 mod entity;
+mod entity_map;
+mod notification;
+mod query;
+mod prefab;
+#[cfg(feature = "serialize")]
+mod serde_registry;
 
 
-pub use component_manager::ComponentManager;
-pub use component::Component;
-pub use entity::Entity;
-pub use scene::Scene;
+pub use dependency_error::DependencyError;
+pub use hierarchy_error::HierarchyError;
+pub use registry_error::RegistryError;
+pub use scene_error::SceneError;
+pub use component_manager::{ComponentManager, ComponentManagerBase};
+pub use component::{Component, ComponentBase};
+pub use component_ref::{ComponentRef, ComponentRefMut};
+pub use component_registry::ComponentRegistry;
+pub use entity_id::EntityId;
+pub use entity_iter::{Children, Siblings, Ancestors, Descendants, DescendantsBreadthFirst};
+pub use entity_map::EntityMap;
+pub use entity::{Entity, WeakEntity, DEFAULT_LAYER_MASK};
+pub use scene::{Scene, WeakScene};
+pub use query::{Fetch, Filter, With, Without, Changed, Query};
+pub use prefab::Prefab;
+#[cfg(feature = "serialize")]
+pub use serde_registry::{SerdeRegistry, SerdeError, SceneData, EntityData, ComponentData};
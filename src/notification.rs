@@ -0,0 +1,77 @@
+use alloc::boxed::Box;
+
+use entity::{self, Entity};
+use component::{Component, ComponentBase};
+use component_manager::{ComponentManager, ComponentManagerBase};
+
+
+/// a change waiting to be handed to a `ComponentManager`, queued on the scene
+/// when the manager is locked, like during its own update, enabling and
+/// disabling also add and remove the entity's components so managers only
+/// ever hold the components of active entities
+pub enum Notification {
+    ParentChanged(Entity, Option<Entity>),
+    ChildAdded(Entity, Entity),
+    ChildRemoved(Entity, Entity),
+    DepthChanged(Entity, usize),
+    Enable(Entity),
+    Disable(Entity),
+    /// a component added to the entity, found by `entity::component_key`
+    AddComponent(Entity, usize),
+    /// a component that stays on the entity, like when it leaves the scene
+    RemoveComponent(Entity, usize),
+    /// a component taken off its entity, it is detached once it is delivered
+    RemovedComponent(Box<Component>),
+}
+
+impl Notification {
+    pub fn deliver(self, component_manager: &mut ComponentManager) {
+        let component_manager_type_id = ComponentManagerBase::type_id(&*component_manager);
+
+        match self {
+            Notification::ParentChanged(entity, prev_parent) => component_manager.on_parent_changed(&entity, prev_parent.as_ref()),
+            Notification::ChildAdded(parent, child) => component_manager.on_child_added(&parent, &child),
+            Notification::ChildRemoved(parent, child) => component_manager.on_child_removed(&parent, &child),
+            Notification::DepthChanged(entity, depth) => component_manager.on_depth_changed(&entity, depth),
+            Notification::Enable(entity) => {
+                entity::each_component_mut(&entity, &component_manager_type_id, |component| {
+                    entity::register_component(component_manager, component);
+                });
+                component_manager.on_enable(&entity);
+            },
+            Notification::Disable(entity) => {
+                component_manager.on_disable(&entity);
+                entity::each_component_mut(&entity, &component_manager_type_id, |component| {
+                    entity::unregister_component(component_manager, component);
+                });
+            },
+            Notification::AddComponent(entity, key) => {
+                entity::each_component_mut(&entity, &component_manager_type_id, |component| {
+                    if entity::component_key(component) == key {
+                        entity::register_component(component_manager, component);
+                    }
+                });
+            },
+            Notification::RemoveComponent(entity, key) => {
+                entity::each_component_mut(&entity, &component_manager_type_id, |component| {
+                    if entity::component_key(component) == key {
+                        entity::unregister_component(component_manager, component);
+                    }
+                });
+            },
+            Notification::RemovedComponent(mut component) => {
+                entity::unregister_component(component_manager, &mut component);
+                component.on_detach();
+                component.set_entity(None);
+            },
+        }
+    }
+
+    /// the manager is gone, components taken off their entity are still detached
+    pub fn discard(self) {
+        if let Notification::RemovedComponent(mut component) = self {
+            component.on_detach();
+            component.set_entity(None);
+        }
+    }
+}
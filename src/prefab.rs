@@ -0,0 +1,236 @@
+use alloc::vec::Vec;
+
+use core::any::TypeId;
+
+use vector::Vector;
+use stack::Stack;
+use remove::Remove;
+use iterable::Iterable;
+
+use entity::{self, Entity, WeakEntity};
+use entity_map;
+use scene::Scene;
+use scene_error::SceneError;
+use component::Component;
+
+
+struct Override {
+    entity: WeakEntity,
+    component: TypeId,
+    index: usize,
+    field: &'static str,
+}
+
+pub struct Prefab {
+    root: Entity,
+    instances: Vector<Vector<(Entity, WeakEntity)>>,
+    overrides: Vector<Override>,
+}
+
+impl Prefab {
+    /// `root` is the template subtree, it should not be added to a scene
+    pub fn new(root: Entity) -> Self {
+        Prefab {
+            root: root,
+            instances: Vector::new(),
+            overrides: Vector::new(),
+        }
+    }
+
+    pub fn root(&self) -> &Entity {
+        &self.root
+    }
+
+    pub fn instantiate(&mut self, scene: Option<&mut Scene>) -> Result<Entity, SceneError> {
+        let (copy, entity_map) = entity::instantiate(&self.root, scene)?;
+        let mut instance = Vector::new();
+
+        for &(ref template, ref entity) in entity_map::entities(&entity_map).iter() {
+            instance.push((template.clone(), entity.downgrade()));
+        }
+        self.instances.push(instance);
+
+        Ok(copy)
+    }
+    pub fn instances(&self) -> Vec<Entity> {
+        let mut instances = Vec::new();
+
+        for instance in self.instances.iter() {
+            if let Some(root) = instance.iter().next().and_then(|&(_, ref entity)| entity.upgrade()) {
+                instances.push(root);
+            }
+        }
+        instances
+    }
+
+    pub fn is_overridden<T: Component>(&self, entity: &Entity, field: &str) -> bool {
+        self.is_overridden_at::<T>(entity, 0usize, field)
+    }
+    pub fn is_overridden_at<T: Component>(&self, entity: &Entity, index: usize, field: &str) -> bool {
+        self.override_index(entity, &TypeId::of::<T>(), index, field).is_some()
+    }
+    pub fn override_field<T: Component>(&mut self, entity: &Entity, field: &'static str) -> &mut Self {
+        self.override_field_at::<T>(entity, 0usize, field)
+    }
+    pub fn override_field_at<T: Component>(&mut self, entity: &Entity, index: usize, field: &'static str) -> &mut Self {
+        if !self.is_overridden_at::<T>(entity, index, field) {
+            self.overrides.push(Override {
+                entity: entity.downgrade(),
+                component: TypeId::of::<T>(),
+                index: index,
+                field: field,
+            });
+        }
+        self
+    }
+    pub fn revert_field<T: Component>(&mut self, entity: &Entity, field: &str) -> &mut Self {
+        self.revert_field_at::<T>(entity, 0usize, field)
+    }
+    /// copies the template's value of `field` back to `entity`, changes to other
+    /// instances that were not recorded yet are left alone
+    pub fn revert_field_at<T: Component>(&mut self, entity: &Entity, index: usize, field: &str) -> &mut Self {
+        let id = TypeId::of::<T>();
+
+        if let Some(override_index) = self.override_index(entity, &id, index, field) {
+            self.overrides.remove(&override_index);
+
+            if let Some(template) = self.template(entity) {
+                if let (Some(template_component), Some(component)) = (
+                    entity::boxed_component(&template, &id, index),
+                    entity::boxed_component_mut(entity, &id, index),
+                ) {
+                    component.copy_field(field, &**template_component);
+                }
+            }
+        }
+        self
+    }
+
+    /// records every field instances changed from the template as overridden,
+    /// runs `f` on the template and then applies it to the instances
+    pub fn edit<F: FnOnce(&mut Entity)>(&mut self, f: F) -> &mut Self {
+        self.record_overrides();
+        f(&mut self.root);
+        self.apply()
+    }
+
+    pub fn apply(&mut self) -> &mut Self {
+        self.remove_dropped();
+
+        for instance in self.instances.iter() {
+            for &(ref template, ref entity) in instance.iter() {
+                let entity = match entity.upgrade() {
+                    Some(entity) => entity,
+                    None => continue,
+                };
+
+                for id in entity::component_type_ids(template).iter() {
+                    for index in 0..entity::component_count(template, id) {
+                        let template_component = match entity::boxed_component(template, id, index) {
+                            Some(component) => component,
+                            None => continue,
+                        };
+
+                        for field in template_component.prefab_fields().iter() {
+                            let differs = match entity::boxed_component(&entity, id, index) {
+                                Some(component) => !component.field_eq(field, &**template_component),
+                                None => false,
+                            };
+
+                            if differs && self.override_index(&entity, id, index, field).is_none() {
+                                if let Some(component) = entity::boxed_component_mut(&entity, id, index) {
+                                    component.copy_field(field, &**template_component);
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        self
+    }
+
+    fn record_overrides(&mut self) {
+        let mut overrides = Vec::new();
+
+        for instance in self.instances.iter() {
+            for &(ref template, ref entity) in instance.iter() {
+                let entity = match entity.upgrade() {
+                    Some(entity) => entity,
+                    None => continue,
+                };
+
+                for id in entity::component_type_ids(template).iter() {
+                    for index in 0..entity::component_count(template, id) {
+                        let (template_component, component) = match (
+                            entity::boxed_component(template, id, index),
+                            entity::boxed_component(&entity, id, index),
+                        ) {
+                            (Some(template_component), Some(component)) => (template_component, component),
+                            _ => continue,
+                        };
+
+                        for field in template_component.prefab_fields().iter() {
+                            if !component.field_eq(field, &**template_component) &&
+                                self.override_index(&entity, id, index, field).is_none()
+                            {
+                                overrides.push(Override {
+                                    entity: entity.downgrade(),
+                                    component: *id,
+                                    index: index,
+                                    field: *field,
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for o in overrides {
+            self.overrides.push(o);
+        }
+    }
+
+    fn remove_dropped(&mut self) {
+        let mut index = self.overrides.len();
+
+        while index > 0usize {
+            index -= 1usize;
+
+            if self.overrides[index].entity.upgrade().is_none() {
+                self.overrides.remove(&index);
+            }
+        }
+
+        let mut index = self.instances.len();
+
+        while index > 0usize {
+            index -= 1usize;
+
+            if !self.instances[index].iter().any(|&(_, ref entity)| entity.upgrade().is_some()) {
+                self.instances.remove(&index);
+            }
+        }
+    }
+
+    fn template(&self, entity: &Entity) -> Option<Entity> {
+        for instance in self.instances.iter() {
+            for &(ref template, ref instance_entity) in instance.iter() {
+                if instance_entity.upgrade().as_ref() == Some(entity) {
+                    return Some(template.clone());
+                }
+            }
+        }
+        None
+    }
+
+    fn override_index(&self, entity: &Entity, component: &TypeId, index: usize, field: &str) -> Option<usize> {
+        self.overrides.iter().position(|o| {
+            &o.component == component &&
+                o.index == index &&
+                o.field == field &&
+                o.entity.upgrade().as_ref() == Some(entity)
+        })
+    }
+}
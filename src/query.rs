@@ -0,0 +1,225 @@
+use alloc::vec::{self, Vec};
+
+use core::any::TypeId;
+use core::marker::PhantomData;
+
+use entity::{self, Entity};
+use component::Component;
+use component_ref::{ComponentRef, ComponentRefMut};
+
+
+mod sealed {
+    pub trait Sealed {}
+}
+
+/// implemented for `&T`, `&mut T`, `Option` of those and tuples of them, `&T`
+/// fetches a `ComponentRef` and `&mut T` a `ComponentRefMut` which keep the
+/// component borrowed until they are dropped
+pub trait Fetch<'a>: sealed::Sealed {
+    type Item;
+    /// what the query yields, the entity followed by each fetched component
+    type Row;
+
+    fn fetch(entity: &Entity) -> Option<Self::Item>;
+    fn row(entity: Entity, item: Self::Item) -> Self::Row;
+    fn access(reads: &mut Vec<TypeId>, writes: &mut Vec<TypeId>);
+}
+
+impl<'a, T: Component> sealed::Sealed for &'a T {}
+
+impl<'a, T: Component> Fetch<'a> for &'a T {
+    type Item = ComponentRef<T>;
+    type Row = (Entity, ComponentRef<T>);
+
+    fn fetch(entity: &Entity) -> Option<Self::Item> {
+        entity::borrow_component::<T>(entity)
+    }
+    fn row(entity: Entity, item: Self::Item) -> Self::Row {
+        (entity, item)
+    }
+    fn access(reads: &mut Vec<TypeId>, _: &mut Vec<TypeId>) {
+        reads.push(TypeId::of::<T>());
+    }
+}
+
+impl<'a, T: Component> sealed::Sealed for &'a mut T {}
+
+impl<'a, T: Component> Fetch<'a> for &'a mut T {
+    type Item = ComponentRefMut<T>;
+    type Row = (Entity, ComponentRefMut<T>);
+
+    fn fetch(entity: &Entity) -> Option<Self::Item> {
+        entity::borrow_component_mut::<T>(entity)
+    }
+    fn row(entity: Entity, item: Self::Item) -> Self::Row {
+        (entity, item)
+    }
+    fn access(_: &mut Vec<TypeId>, writes: &mut Vec<TypeId>) {
+        writes.push(TypeId::of::<T>());
+    }
+}
+
+impl<'a, F: Fetch<'a>> sealed::Sealed for Option<F> {}
+
+impl<'a, F: Fetch<'a>> Fetch<'a> for Option<F> {
+    type Item = Option<F::Item>;
+    type Row = (Entity, Option<F::Item>);
+
+    fn fetch(entity: &Entity) -> Option<Self::Item> {
+        Some(F::fetch(entity))
+    }
+    fn row(entity: Entity, item: Self::Item) -> Self::Row {
+        (entity, item)
+    }
+    fn access(reads: &mut Vec<TypeId>, writes: &mut Vec<TypeId>) {
+        F::access(reads, writes);
+    }
+}
+
+macro_rules! impl_fetch_tuple {
+    ($($name:ident),+) => (
+        impl<'a, $($name: Fetch<'a>),+> sealed::Sealed for ($($name,)+) {}
+
+        impl<'a, $($name: Fetch<'a>),+> Fetch<'a> for ($($name,)+) {
+            type Item = ($($name::Item,)+);
+            type Row = (Entity, $($name::Item,)+);
+
+            fn fetch(entity: &Entity) -> Option<Self::Item> {
+                Some(($(match $name::fetch(entity) {
+                    Some(item) => item,
+                    None => return None,
+                },)+))
+            }
+            #[allow(non_snake_case)]
+            fn row(entity: Entity, item: Self::Item) -> Self::Row {
+                let ($($name,)+) = item;
+                (entity, $($name,)+)
+            }
+            fn access(reads: &mut Vec<TypeId>, writes: &mut Vec<TypeId>) {
+                $($name::access(reads, writes);)+
+            }
+        }
+    );
+}
+
+impl_fetch_tuple!(A);
+impl_fetch_tuple!(A, B);
+impl_fetch_tuple!(A, B, C);
+impl_fetch_tuple!(A, B, C, D);
+impl_fetch_tuple!(A, B, C, D, E);
+impl_fetch_tuple!(A, B, C, D, E, F);
+impl_fetch_tuple!(A, B, C, D, E, F, G);
+impl_fetch_tuple!(A, B, C, D, E, F, G, H);
+
+
+pub trait Filter {
+    fn matches(entity: &Entity) -> bool;
+}
+
+impl Filter for () {
+    fn matches(_: &Entity) -> bool {
+        true
+    }
+}
+
+pub struct With<T: Component>(PhantomData<T>);
+
+impl<T: Component> Filter for With<T> {
+    fn matches(entity: &Entity) -> bool {
+        entity.has_component::<T>()
+    }
+}
+
+pub struct Without<T: Component>(PhantomData<T>);
+
+impl<T: Component> Filter for Without<T> {
+    fn matches(entity: &Entity) -> bool {
+        !entity.has_component::<T>()
+    }
+}
+
+/// matches entities whose `T` was added, borrowed with `component_mut` or written
+/// through a query since the manager running the query last ran, outside of
+/// updates since the end of the last `Scene::update`
+pub struct Changed<T: Component>(PhantomData<T>);
+
+impl<T: Component> Filter for Changed<T> {
+    fn matches(entity: &Entity) -> bool {
+        entity.is_changed::<T>()
+    }
+}
+
+macro_rules! impl_filter_tuple {
+    ($($name:ident),+) => (
+        impl<$($name: Filter),+> Filter for ($($name,)+) {
+            fn matches(entity: &Entity) -> bool {
+                $($name::matches(entity))&&+
+            }
+        }
+    );
+}
+
+impl_filter_tuple!(A);
+impl_filter_tuple!(A, B);
+impl_filter_tuple!(A, B, C);
+impl_filter_tuple!(A, B, C, D);
+impl_filter_tuple!(A, B, C, D, E);
+impl_filter_tuple!(A, B, C, D, E, F);
+impl_filter_tuple!(A, B, C, D, E, F, G);
+impl_filter_tuple!(A, B, C, D, E, F, G, H);
+
+
+pub struct Query<'a, Q: Fetch<'a>, F: Filter = ()> {
+    entities: vec::IntoIter<Entity>,
+    root: Option<Entity>,
+    phantom_data: PhantomData<(&'a (), Q, F)>,
+}
+
+pub fn new<'a, Q: Fetch<'a>, F: Filter>(entities: Vec<Entity>) -> Query<'a, Q, F> {
+    let mut reads = Vec::new();
+    let mut writes = Vec::new();
+
+    Q::access(&mut reads, &mut writes);
+
+    for (index, write) in writes.iter().enumerate() {
+        if writes[..index].contains(write) || reads.contains(write) {
+            panic!("query borrows component {:?} mutably while it is already borrowed", write);
+        }
+    }
+
+    Query {
+        entities: entities.into_iter(),
+        root: None,
+        phantom_data: PhantomData,
+    }
+}
+
+impl<'a, Q: Fetch<'a>, F: Filter> Query<'a, Q, F> {
+    pub fn within(mut self, root: &Entity) -> Self {
+        self.root = Some(root.clone());
+        self
+    }
+}
+
+/// yields `(Entity, A, B, ..)` rows, a component borrowed by a row can not be
+/// borrowed again, removed or replaced until the row is dropped
+impl<'a, Q: Fetch<'a>, F: Filter> Iterator for Query<'a, Q, F> {
+    type Item = Q::Row;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(entity) = self.entities.next() {
+            if let Some(ref root) = self.root {
+                if !entity::in_subtree(&entity, root) {
+                    continue;
+                }
+            }
+            if !F::matches(&entity) {
+                continue;
+            }
+            if let Some(item) = Q::fetch(&entity) {
+                return Some(Q::row(entity, item));
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,20 @@
+use alloc::string::String;
+
+use core::any::TypeId;
+use core::fmt;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryError {
+    DuplicateName(String),
+    DuplicateType(TypeId),
+}
+
+impl fmt::Display for RegistryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &RegistryError::DuplicateName(ref name) => write!(f, "a type is already registered as {:?}", name),
+            &RegistryError::DuplicateType(ref type_id) => write!(f, "type {:?} is already registered", type_id),
+        }
+    }
+}
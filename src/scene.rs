@@ -1,7 +1,9 @@
 use alloc::boxed::Box;
 use alloc::arc::Arc;
+use alloc::vec::Vec;
+use alloc::string::String;
 
-use core::sync::atomic::{AtomicBool, Ordering};
+use core::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 use core::any::TypeId;
 use core::mem;
 
@@ -17,34 +19,86 @@ use vector::Vector;
 use stack::Stack;
 use remove::Remove;
 
-use shared::Shared;
+use handle::{Handle, WeakHandle};
 
+use dependency_error::DependencyError;
+use entity_id::EntityId;
+use hierarchy_error::HierarchyError;
+use scene_error::SceneError;
 use entity::{self, Entity};
-use component::Component;
-use component_manager::ComponentManager;
+use component::{Component, ComponentBase};
+use component_manager::{ComponentManager, ComponentManagerBase};
+use notification::Notification;
+use query::{self, Fetch, Filter, Query};
 
 
+struct Slot {
+    generation: u32,
+    entity: Option<Entity>,
+}
+
+/// order and dependencies are read once when the manager is added, so they can
+/// be looked up while the manager is locked
+struct ComponentManagerEntry {
+    component_manager: Arc<RwLock<Box<ComponentManager>>>,
+    name: &'static str,
+    order: isize,
+    dependencies: Vec<TypeId>,
+    last_change_tick: usize,
+}
+
 struct SceneInner {
     initted: AtomicBool,
     entities: Arc<RwLock<Vector<Entity>>>,
-    component_managers: Arc<RwLock<HashMap<TypeId, Arc<RwLock<Box<ComponentManager>>>>>>,
+    slots: Arc<RwLock<Vector<Slot>>>,
+    free_slots: Arc<RwLock<Vector<u32>>>,
+    tags: Arc<RwLock<HashMap<String, Vector<Entity>>>>,
+    layers: Arc<RwLock<HashMap<usize, Vector<Entity>>>>,
+    component_managers: Arc<RwLock<HashMap<TypeId, ComponentManagerEntry>>>,
+    component_manager_order: Arc<RwLock<Vector<TypeId>>>,
+    sorted_component_managers: Arc<RwLock<Vector<TypeId>>>,
+    component_managers_sorted: AtomicBool,
+    notifications: Arc<RwLock<Vec<(TypeId, Notification)>>>,
+    component_counts: Arc<RwLock<HashMap<TypeId, usize>>>,
+    change_tick: AtomicUsize,
+    last_change_tick: AtomicUsize,
 }
 
 #[derive(Clone)]
 pub struct Scene {
-    inner: Shared<SceneInner>,
+    inner: Handle<SceneInner>,
+}
+
+#[derive(Clone)]
+pub struct WeakScene {
+    inner: WeakHandle<SceneInner>,
 }
 
 impl Scene {
     pub fn new() -> Self {
         Scene {
-            inner: unsafe {
-                Shared::new(Box::into_raw(Box::new(SceneInner {
-                    initted: AtomicBool::new(false),
-                    entities: Arc::new(RwLock::new(Vector::new())),
-                    component_managers: Arc::new(RwLock::new(HashMap::new())),
-                })))
-            }
+            inner: Handle::new(SceneInner {
+                initted: AtomicBool::new(false),
+                entities: Arc::new(RwLock::new(Vector::new())),
+                slots: Arc::new(RwLock::new(Vector::new())),
+                free_slots: Arc::new(RwLock::new(Vector::new())),
+                tags: Arc::new(RwLock::new(HashMap::new())),
+                layers: Arc::new(RwLock::new(HashMap::new())),
+                component_managers: Arc::new(RwLock::new(HashMap::new())),
+                component_manager_order: Arc::new(RwLock::new(Vector::new())),
+                sorted_component_managers: Arc::new(RwLock::new(Vector::new())),
+                component_managers_sorted: AtomicBool::new(true),
+                notifications: Arc::new(RwLock::new(Vec::new())),
+                component_counts: Arc::new(RwLock::new(HashMap::new())),
+                change_tick: AtomicUsize::new(1usize),
+                last_change_tick: AtomicUsize::new(0usize),
+            }),
+        }
+    }
+
+    pub fn downgrade(&self) -> WeakScene {
+        WeakScene {
+            inner: self.inner.downgrade(),
         }
     }
 
@@ -56,50 +110,214 @@ impl Scene {
         }
     }
 
-    pub fn init(&mut self) -> &mut Self {
+    pub fn init(&mut self) -> Result<&mut Self, SceneError> {
         if let Some(inner) = unsafe {self.inner.as_mut()} {
             if inner.initted.load(Ordering::Relaxed) {
-                inner.initted.store(true, Ordering::Relaxed)
+                return Err(SceneError::AlreadyInitted);
+            }
+            let component_managers = component_managers(inner)?;
+
+            inner.initted.store(true, Ordering::Relaxed);
+
+            for &(_, ref component_manager) in component_managers.iter() {
+                component_manager.write().init();
+                flush_notifications(inner);
             }
         }
-        self
+        Ok(self)
+    }
+
+    pub fn destroy(&mut self) -> Result<&mut Self, SceneError> {
+        if let Some(inner) = unsafe {self.inner.as_mut()} {
+            if !inner.initted.load(Ordering::Relaxed) {
+                return Err(SceneError::NotInitted);
+            }
+            inner.initted.store(false, Ordering::Relaxed);
+            destroy_component_managers(inner);
+        }
+        Ok(self)
+    }
+
+    pub fn update(&mut self, dt: f64) -> Result<&mut Self, SceneError> {
+        if let Some(inner) = unsafe {self.inner.as_mut()} {
+            if !inner.initted.load(Ordering::Relaxed) {
+                return Err(SceneError::NotInitted);
+            }
+
+            for &(ref type_id, ref component_manager) in component_managers(inner)?.iter() {
+                let last_change_tick = inner.component_managers.read().get(type_id).map(|entry| entry.last_change_tick);
+
+                // managers removed during an earlier manager's update are skipped
+                if let Some(last_change_tick) = last_change_tick {
+                    // while a manager updates `Changed` reports what changed since it last ran
+                    let outer_change_tick = inner.last_change_tick.swap(last_change_tick, Ordering::Relaxed);
+                    component_manager.write().update(dt);
+                    inner.last_change_tick.store(outer_change_tick, Ordering::Relaxed);
+
+                    let change_tick = inner.change_tick.fetch_add(1usize, Ordering::Relaxed);
+
+                    if let Some(entry) = inner.component_managers.write().get_mut(type_id) {
+                        entry.last_change_tick = change_tick;
+                    }
+                }
+                flush_notifications(inner);
+            }
+            // outside of updates `Changed` reports what changed since this update
+            let change_tick = inner.change_tick.fetch_add(1usize, Ordering::Relaxed);
+            inner.last_change_tick.store(change_tick, Ordering::Relaxed);
+        }
+        Ok(self)
+    }
+
+    pub fn sort_component_managers(&mut self) -> Result<&mut Self, DependencyError> {
+        if let Some(inner) = unsafe {self.inner.as_mut()} {
+            sort_component_managers(inner)?;
+        }
+        Ok(self)
     }
 
     pub fn clear(&mut self) -> &mut Self {
         if let Some(inner) = unsafe {self.inner.as_mut()} {
+            if inner.initted.load(Ordering::Relaxed) {
+                inner.initted.store(false, Ordering::Relaxed);
+                destroy_component_managers(inner);
+            }
+
             for entity in inner.entities.write().iter_mut() {
+                if let Some(id) = entity.id() {
+                    free_slot(inner, id);
+                }
                 entity.clear();
             }
 
+            inner.tags.write().clear();
+            inner.layers.write().clear();
             inner.component_managers.write().clear();
+            inner.component_manager_order.write().clear();
+            inner.sorted_component_managers.write().clear();
+            inner.component_managers_sorted.store(true, Ordering::Relaxed);
+            inner.notifications.write().clear();
+            inner.component_counts.write().clear();
             inner.entities.write().clear();
         }
         self
     }
 
-    pub fn add_entity(&mut self, mut entity: Entity) -> &mut Self {
+    pub fn add_entity(&mut self, entity: Entity) -> Result<&mut Self, SceneError> {
+        match entity.scene() {
+            Some(ref scene) if scene == self => (),
+            Some(_) => return Err(SceneError::Hierarchy(HierarchyError::DifferentScene)),
+            None => add_entity(self, entity)?,
+        }
+        Ok(self)
+    }
+    pub fn remove_entity(&mut self, entity: &mut Entity) -> Result<&mut Self, SceneError> {
+        if entity.scene().as_ref() != Some(&*self) {
+            return Err(SceneError::Hierarchy(HierarchyError::DifferentScene));
+        }
+        remove_entity(self, entity)?;
+        Ok(self)
+    }
 
-        entity::set_scene(&mut entity, self);
+    pub fn get(&self, id: EntityId) -> Option<Entity> {
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            let slots = inner.slots.read();
+            let index = id.index() as usize;
 
-        if let Some(inner) = unsafe {self.inner.as_mut()} {
-            inner.entities.write().push(entity);
+            if index < slots.len() && slots[index].generation == id.generation() {
+                slots[index].entity.clone()
+            } else {
+                None
+            }
+        } else {
+            None
         }
+    }
+    pub fn contains(&self, id: EntityId) -> bool {
+        self.get(id).is_some()
+    }
 
-        self
+    pub fn query<'a, Q: Fetch<'a>>(&'a self) -> Query<'a, Q> {
+        self.query_filtered::<Q, ()>()
+    }
+    pub fn query_filtered<'a, Q: Fetch<'a>, F: Filter>(&'a self) -> Query<'a, Q, F> {
+        let mut entities = Vec::new();
+
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            for entity in inner.entities.read().iter() {
+                if entity.active_in_hierarchy() {
+                    entities.push(entity.clone());
+                }
+            }
+        }
+        query::new(entities)
     }
-    pub fn remove_entity(&mut self, entity: &mut Entity) -> &mut Self {
 
-        entity::remove_scene(entity, self);
+    pub fn entities_with_tag(&self, tag: &str, root: Option<&Entity>) -> Vec<Entity> {
+        let mut entities = Vec::new();
 
-        if let Some(inner) = unsafe {self.inner.as_mut()} {
-            let mut entities = inner.entities.write();
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            if let Some(tagged) = inner.tags.read().get(&String::from(tag)) {
+                for entity in tagged.iter() {
+                    if entity.active_in_hierarchy() &&
+                        root.map_or(true, |root| entity::in_subtree(entity, root))
+                    {
+                        entities.push(entity.clone());
+                    }
+                }
+            }
+        }
+        entities
+    }
+    pub fn entities_in_layers(&self, layer_mask: u32, root: Option<&Entity>) -> Vec<Entity> {
+        let mut entities = Vec::new();
 
-            if let Some(index) = entities.iter().position(|e| e == entity) {
-                entities.remove(&index);
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            let layers = inner.layers.read();
+
+            for layer in 0..32usize {
+                let bit = 1u32 << layer;
+
+                if (layer_mask & bit) == 0u32 {
+                    continue;
+                }
+                if let Some(layered) = layers.get(&layer) {
+                    // entities in several of the requested layers are only
+                    // collected from the lowest one
+                    let lower_mask = layer_mask & (bit - 1u32);
+
+                    for entity in layered.iter() {
+                        if (entity.layer_mask() & lower_mask) == 0u32 &&
+                            entity.active_in_hierarchy() &&
+                            root.map_or(true, |root| entity::in_subtree(entity, root))
+                        {
+                            entities.push(entity.clone());
+                        }
+                    }
+                }
             }
         }
+        entities
+    }
 
-        self
+    pub fn find_path(&self, path: &str) -> Option<Entity> {
+        if let Some(inner) = unsafe {self.inner.as_ref()} {
+            let path = if path.starts_with('/') { &path[1..] } else { path };
+            let (name, rest) = match path.find('/') {
+                Some(index) => (&path[..index], &path[index..]),
+                None => (path, ""),
+            };
+            let root = inner.entities.read().iter()
+                .find(|entity| entity.parent().is_none() && entity.name() == name)
+                .map(|entity| entity.clone());
+
+            match root {
+                Some(root) => root.find_path(rest),
+                None => None,
+            }
+        } else {
+            None
+        }
     }
 
     pub fn has_component_manager<T: ComponentManager>(&self) -> bool {
@@ -109,84 +327,685 @@ impl Scene {
             false
         }
     }
-    pub fn component_manager<T: ComponentManager>(&self) -> Option<Arc<RwLock<T>>> {
+    pub fn component_manager<T: ComponentManager>(&self) -> Option<Arc<RwLock<Box<ComponentManager>>>> {
+        component_manager(self, &TypeId::of::<T>())
+    }
+    pub fn try_component_manager<T: ComponentManager>(&self) -> Result<Arc<RwLock<Box<ComponentManager>>>, SceneError> {
+        self.component_manager::<T>().ok_or(SceneError::ComponentManagerNotFound(TypeId::of::<T>()))
+    }
+    pub fn with_component_manager<T: ComponentManager, F: FnOnce(&T) -> R, R>(&self, f: F) -> Option<R> {
+        match self.component_manager::<T>() {
+            Some(component_manager) => component_manager.read().downcast_ref::<T>().map(f),
+            None => None,
+        }
+    }
+    /// changes made in `f` that had to be queued for the manager are delivered after it
+    pub fn with_component_manager_mut<T: ComponentManager, F: FnOnce(&mut T) -> R, R>(&self, f: F) -> Option<R> {
+        let result = match self.component_manager::<T>() {
+            Some(component_manager) => component_manager.write().downcast_mut::<T>().map(f),
+            None => None,
+        };
         if let Some(inner) = unsafe {self.inner.as_ref()} {
-            if let Some(component_manager) = inner.component_managers.read().get(&TypeId::of::<T>()) {
-                Some(unsafe {mem::transmute(component_manager.clone())})
-            } else {
-                None
+            flush_notifications(inner);
+        }
+        result
+    }
+}
+
+fn component_managers(inner: &SceneInner) -> Result<Vector<(TypeId, Arc<RwLock<Box<ComponentManager>>>)>, DependencyError> {
+    if !inner.component_managers_sorted.load(Ordering::Relaxed) {
+        sort_component_managers(inner)?;
+    }
+
+    let mut component_managers = Vector::new();
+    let map = inner.component_managers.read();
+
+    for type_id in inner.sorted_component_managers.read().iter() {
+        if let Some(entry) = map.get(type_id) {
+            component_managers.push((*type_id, entry.component_manager.clone()));
+        }
+    }
+    Ok(component_managers)
+}
+
+/// delivers queued notifications until none are left, notifications for a manager
+/// that is still locked are kept in order for the next flush
+fn flush_notifications(inner: &SceneInner) {
+    loop {
+        let notifications = mem::replace(&mut *inner.notifications.write(), Vec::new());
+        let count = notifications.len();
+        let mut pending = Vec::new();
+
+        for (component_manager_type_id, notification) in notifications {
+            match component_manager_entry(inner, &component_manager_type_id) {
+                Some(component_manager) => match component_manager.try_write() {
+                    Some(mut component_manager) => notification.deliver(&mut **component_manager),
+                    None => pending.push((component_manager_type_id, notification)),
+                },
+                None => notification.discard(),
             }
-        } else {
-            None
         }
+
+        let mut notifications = inner.notifications.write();
+        let done = notifications.len() == 0usize && pending.len() == count;
+        let queued = mem::replace(&mut *notifications, pending);
+
+        notifications.extend(queued);
+
+        if done || notifications.len() == 0usize {
+            break;
+        }
+    }
+    remove_empty_component_managers(inner);
+}
+
+fn destroy_component_managers(inner: &SceneInner) {
+    let component_managers = match component_managers(inner) {
+        Ok(component_managers) => component_managers,
+        Err(_) => {
+            let mut component_managers = Vector::new();
+            let map = inner.component_managers.read();
+
+            for type_id in inner.component_manager_order.read().iter() {
+                if let Some(entry) = map.get(type_id) {
+                    component_managers.push((*type_id, entry.component_manager.clone()));
+                }
+            }
+            component_managers
+        },
+    };
+    let mut index = component_managers.len();
+
+    while index > 0 {
+        index -= 1;
+        component_managers[index].1.write().destroy();
+    }
+}
+
+fn sort_component_managers(inner: &SceneInner) -> Result<(), DependencyError> {
+    let map = inner.component_managers.read();
+    let mut pending = Vector::new();
+
+    for type_id in inner.component_manager_order.read().iter() {
+        if let Some(entry) = map.get(type_id) {
+            let dependencies = entry.dependencies.clone();
+
+            for dependency in dependencies.iter() {
+                if !map.contains_key(dependency) {
+                    return Err(DependencyError::MissingDependency {
+                        component_manager: *type_id,
+                        name: entry.name,
+                        dependency: *dependency,
+                    });
+                }
+            }
+            pending.push((*type_id, dependencies));
+        }
+    }
+
+    let mut sorted: Vector<TypeId> = Vector::new();
+
+    while pending.len() != 0 {
+        let ready = pending.iter().position(|&(_, ref dependencies)| {
+            dependencies.iter().all(|dependency| sorted.iter().any(|type_id| type_id == dependency))
+        });
+
+        match ready {
+            Some(index) => {
+                let type_id = pending[index].0;
+                pending.remove(&index);
+                sorted.push(type_id);
+            },
+            None => {
+                let component_managers = find_cycle(&pending, &sorted);
+                let names = component_managers.iter()
+                    .map(|type_id| map.get(type_id).map_or("", |entry| entry.name))
+                    .collect();
+
+                return Err(DependencyError::Cycle {
+                    component_managers: component_managers,
+                    names: names,
+                });
+            },
+        }
+    }
+
+    *inner.sorted_component_managers.write() = sorted;
+    inner.component_managers_sorted.store(true, Ordering::Relaxed);
+
+    Ok(())
+}
+
+fn find_cycle(pending: &Vector<(TypeId, Vec<TypeId>)>, sorted: &Vector<TypeId>) -> Vec<TypeId> {
+    let mut path: Vec<TypeId> = Vec::new();
+    let mut current = pending[0].0;
+
+    loop {
+        if let Some(index) = path.iter().position(|type_id| type_id == &current) {
+            let mut cycle = path.split_off(index);
+            cycle.push(current);
+            return cycle;
+        }
+        path.push(current);
+
+        let &(_, ref dependencies) = pending.iter().find(|&&(type_id, _)| type_id == current).unwrap();
+
+        current = *dependencies.iter()
+            .find(|dependency| !sorted.iter().any(|type_id| type_id == *dependency))
+            .unwrap();
+    }
+}
+
+fn insert_component_manager_order(inner: &SceneInner, type_id: TypeId, order: isize) {
+    let index = {
+        let map = inner.component_managers.read();
+
+        inner.component_manager_order.read().iter().position(|id| {
+            match map.get(id) {
+                Some(entry) => entry.order > order,
+                None => false,
+            }
+        })
+    };
+    let mut component_manager_order = inner.component_manager_order.write();
+
+    inner.component_managers_sorted.store(false, Ordering::Relaxed);
+
+    match index {
+        Some(index) => {
+            component_manager_order.insert(index, type_id);
+        },
+        None => {
+            component_manager_order.push(type_id);
+        },
     }
 }
 
-pub fn add_component_manager<'a>(scene: &'a mut Scene, _component_manager: &'a mut Arc<RwLock<Box<ComponentManager>>>) {
-    if let Some(_inner) = unsafe {scene.inner.as_mut()} {
+fn remove_component_manager_order(inner: &SceneInner, type_id: &TypeId) {
+    let mut component_manager_order = inner.component_manager_order.write();
 
+    inner.component_managers_sorted.store(false, Ordering::Relaxed);
+
+    if let Some(index) = component_manager_order.iter().position(|id| id == type_id) {
+        component_manager_order.remove(&index);
     }
 }
 
-pub fn remove_component_manager<'a>(scene: &'a mut Scene, _component_manager: &'a mut Arc<RwLock<Box<ComponentManager>>>) {
-    if let Some(_inner) = unsafe {scene.inner.as_mut()} {
+pub fn entities(scene: &Scene) -> Vec<Entity> {
+    let mut entities = Vec::new();
 
+    if let Some(inner) = unsafe {scene.inner.as_ref()} {
+        for entity in inner.entities.read().iter() {
+            entities.push(entity.clone());
+        }
     }
+    entities
 }
 
-pub fn add_component<'a>(scene: &'a mut Scene, component: &'a mut Box<Component>) {
+pub fn add_entity<'a>(scene: &'a mut Scene, mut entity: Entity) -> Result<(), SceneError> {
+
     if let Some(inner) = unsafe {scene.inner.as_mut()} {
-        let component_manager_type_id = component.component_manager_type_id();
+        let id = alloc_slot(inner, &entity);
+        entity::set_id(&mut entity, Some(id));
+    }
 
-        if !inner.component_managers.read().contains_key(&component_manager_type_id) {
-            let mut component_manager = component.new_component_manager();
-            component_manager.set_scene(Some(scene.clone()));
-            inner.component_managers.write().insert(
-                component_manager_type_id,
-                Arc::new(RwLock::new(component_manager))
-            );
+    if let Some(tags) = entity::tags(&entity) {
+        for tag in tags.iter() {
+            add_tag(scene, &entity, tag);
         }
-        let mut component_managers = inner.component_managers.write();
-        let mut component_manager = component_managers.get_mut(&component_manager_type_id).unwrap();
+    }
+    add_layers(scene, &entity, entity.layer_mask());
 
-        component_manager.write().add_component(component);
+    entity::set_scene(&mut entity, scene)?;
+
+    if let Some(inner) = unsafe {scene.inner.as_mut()} {
+        inner.entities.write().push(entity);
+    }
+    Ok(())
+}
+
+pub fn remove_entity<'a>(scene: &'a mut Scene, entity: &'a mut Entity) -> Result<(), SceneError> {
+
+    entity::remove_scene(entity, scene)?;
+
+    if let Some(tags) = entity::tags(entity) {
+        for tag in tags.iter() {
+            remove_tag(scene, entity, tag);
+        }
+    }
+    remove_layers(scene, entity, entity.layer_mask());
+
+    if let Some(inner) = unsafe {scene.inner.as_mut()} {
+        if let Some(id) = entity.id() {
+            free_slot(inner, id);
+        }
+        entity::set_id(entity, None);
+
+        let mut entities = inner.entities.write();
+
+        if let Some(index) = entities.iter().position(|e| e == entity) {
+            entities.remove(&index);
+        }
+    }
+    Ok(())
+}
+
+fn alloc_slot(inner: &SceneInner, entity: &Entity) -> EntityId {
+    let mut slots = inner.slots.write();
+
+    match inner.free_slots.write().pop() {
+        Some(index) => {
+            let slot = &mut slots[index as usize];
+            slot.entity = Some(entity.clone());
+            EntityId::new(index, slot.generation)
+        },
+        None => {
+            let index = slots.len() as u32;
+            slots.push(Slot {
+                generation: 0u32,
+                entity: Some(entity.clone()),
+            });
+            EntityId::new(index, 0u32)
+        },
+    }
+}
+
+fn free_slot(inner: &SceneInner, id: EntityId) {
+    let mut slots = inner.slots.write();
+    let index = id.index() as usize;
+
+    if index >= slots.len() {
+        return;
+    }
+    let slot = &mut slots[index];
+
+    if slot.generation == id.generation() {
+        slot.generation = slot.generation.wrapping_add(1u32);
+        slot.entity = None;
+        inner.free_slots.write().push(id.index());
+    }
+}
+
+pub fn add_tag<'a>(scene: &'a mut Scene, entity: &'a Entity, tag: &'a str) {
+    if let Some(inner) = unsafe {scene.inner.as_mut()} {
+        let mut tags = inner.tags.write();
+        let tag = String::from(tag);
+
+        if !tags.contains_key(&tag) {
+            tags.insert(tag.clone(), Vector::new());
+        }
+        tags.get_mut(&tag).unwrap().push(entity.clone());
+    }
+}
+
+pub fn remove_tag<'a>(scene: &'a mut Scene, entity: &'a Entity, tag: &'a str) {
+    if let Some(inner) = unsafe {scene.inner.as_mut()} {
+        let mut tags = inner.tags.write();
+        let tag = String::from(tag);
+        let mut is_empty = false;
+
+        if let Some(tagged) = tags.get_mut(&tag) {
+            if let Some(index) = tagged.iter().position(|e| e == entity) {
+                tagged.remove(&index);
+            }
+            is_empty = tagged.len() == 0;
+        }
+        if is_empty {
+            tags.remove(&tag);
+        }
+    }
+}
+
+pub fn add_layers<'a>(scene: &'a mut Scene, entity: &'a Entity, layer_mask: u32) {
+    if let Some(inner) = unsafe {scene.inner.as_mut()} {
+        let mut layers = inner.layers.write();
+
+        for layer in 0..32usize {
+            if (layer_mask & (1u32 << layer)) != 0u32 {
+                if !layers.contains_key(&layer) {
+                    layers.insert(layer, Vector::new());
+                }
+                layers.get_mut(&layer).unwrap().push(entity.clone());
+            }
+        }
+    }
+}
+
+pub fn remove_layers<'a>(scene: &'a mut Scene, entity: &'a Entity, layer_mask: u32) {
+    if let Some(inner) = unsafe {scene.inner.as_mut()} {
+        let mut layers = inner.layers.write();
+
+        for layer in 0..32usize {
+            if (layer_mask & (1u32 << layer)) != 0u32 {
+                if let Some(layered) = layers.get_mut(&layer) {
+                    if let Some(index) = layered.iter().position(|e| e == entity) {
+                        layered.remove(&index);
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn remove_component_manager(inner: &SceneInner, component_manager_type_id: &TypeId) {
+    if let Some(component_manager) = component_manager_entry(inner, component_manager_type_id) {
+        inner.component_managers.write().remove(component_manager_type_id);
+        remove_component_manager_order(inner, component_manager_type_id);
+
+        let mut component_manager = component_manager.write();
 
         if inner.initted.load(Ordering::Relaxed) {
-            add_component_manager(scene, component_manager);
+            component_manager.destroy();
+        }
+        component_manager.set_scene(None);
+    }
+}
+
+/// removes managers left without components, managers that were locked when
+/// their last component was removed are removed here once they are unlocked
+fn remove_empty_component_managers(inner: &SceneInner) {
+    let mut empty = Vector::new();
+
+    {
+        let component_counts = inner.component_counts.read();
+        let map = inner.component_managers.read();
+
+        for (type_id, entry) in map.iter() {
+            if !component_counts.contains_key(type_id) &&
+                entry.component_manager.try_read().map_or(false, |component_manager| component_manager.is_empty())
+            {
+                empty.push((*type_id, entry.dependencies.clone()));
+            }
+        }
+
+        // managers that a kept manager depends on are kept too, they go once it goes
+        loop {
+            let kept = empty.iter().position(|&(ref type_id, _)| {
+                map.iter().any(|(other, entry)| {
+                    !empty.iter().any(|&(ref id, _)| id == other) &&
+                        entry.dependencies.iter().any(|dependency| dependency == type_id)
+                })
+            });
+
+            match kept {
+                Some(index) => {
+                    empty.remove(&index);
+                },
+                None => break,
+            }
         }
     }
+
+    // managers are destroyed before the managers they depend on
+    while empty.len() != 0 {
+        let index = empty.iter().position(|&(ref type_id, _)| {
+            !empty.iter().any(|&(_, ref dependencies)| dependencies.iter().any(|dependency| dependency == type_id))
+        }).unwrap_or(0);
+
+        if let Some((type_id, _)) = empty.remove(&index) {
+            remove_component_manager(inner, &type_id);
+        }
+    }
+}
+
+fn component_manager_entry(inner: &SceneInner, component_manager_type_id: &TypeId) -> Option<Arc<RwLock<Box<ComponentManager>>>> {
+    inner.component_managers.read().get(component_manager_type_id).map(|entry| entry.component_manager.clone())
 }
 
-pub fn remove_component<'a>(scene: &'a mut Scene, component: &'a mut Box<Component>) {
+/// components of inactive entities are counted but not handed to their manager,
+/// the manager is kept until every component counted for it is removed, a locked
+/// manager gets the component once it is unlocked
+pub fn add_component<'a>(scene: &'a mut Scene, component: &'a mut Box<Component>, active: bool) -> Result<(), SceneError> {
     if let Some(inner) = unsafe {scene.inner.as_mut()} {
         let component_manager_type_id = component.component_manager_type_id();
-        let mut is_empty = false;
 
-        if let Some(component_manager) = inner.component_managers.write().get_mut(&component_manager_type_id) {
-            component_manager.write().remove_component(component);
-            is_empty = component_manager.read().is_empty();
+        let component_manager = match component_manager_entry(inner, &component_manager_type_id) {
+            Some(component_manager) => component_manager,
+            None => {
+                let mut component_manager = component.new_component_manager();
+                component_manager.set_scene(Some(scene.downgrade()));
+
+                let name = component_manager.name();
+                let order = component_manager.order();
+                let dependencies = component_manager.dependencies();
+                insert_component_manager_order(inner, component_manager_type_id, order);
+
+                let component_manager = Arc::new(RwLock::new(component_manager));
+                inner.component_managers.write().insert(component_manager_type_id, ComponentManagerEntry {
+                    component_manager: component_manager.clone(),
+                    name: name,
+                    order: order,
+                    dependencies: dependencies,
+                    last_change_tick: 0usize,
+                });
+
+                // managers added after init are inited right away, before they are handed
+                // their first component, so their dependencies have to be in the scene already
+                if inner.initted.load(Ordering::Relaxed) {
+                    if let Err(error) = sort_component_managers(inner) {
+                        inner.component_managers.write().remove(&component_manager_type_id);
+                        remove_component_manager_order(inner, &component_manager_type_id);
+                        component_manager.write().set_scene(None);
+                        return Err(SceneError::Dependency(error));
+                    }
+                    component_manager.write().init();
+                }
+                component_manager
+            },
+        };
+
+        if active {
+            match component_manager.try_write() {
+                Some(mut component_manager) => {
+                    deliver_pending(inner, &component_manager_type_id, &mut **component_manager);
+                    entity::register_component(&mut **component_manager, component);
+                },
+                None => if let Some(entity) = component.entity() {
+                    let key = entity::component_key(component);
+                    inner.notifications.write().push((component_manager_type_id, Notification::AddComponent(entity, key)));
+                },
+            }
+        }
+        {
+            let mut component_counts = inner.component_counts.write();
+
+            if !component_counts.contains_key(&component_manager_type_id) {
+                component_counts.insert(component_manager_type_id, 0usize);
+            }
+            *component_counts.get_mut(&component_manager_type_id).unwrap() += 1usize;
+        }
+    }
+    Ok(())
+}
+
+/// the tick changes to components are stamped with
+pub fn change_tick(scene: &Scene) -> usize {
+    if let Some(inner) = unsafe {scene.inner.as_ref()} {
+        inner.change_tick.load(Ordering::Relaxed)
+    } else {
+        0usize
+    }
+}
+/// changes stamped after this tick are reported by `Changed`
+pub fn last_change_tick(scene: &Scene) -> usize {
+    if let Some(inner) = unsafe {scene.inner.as_ref()} {
+        inner.last_change_tick.load(Ordering::Relaxed)
+    } else {
+        0usize
+    }
+}
+
+pub fn defer_notification<'a>(scene: &'a mut Scene, component_manager_type_id: TypeId, notification: Notification) {
+    if let Some(inner) = unsafe {scene.inner.as_mut()} {
+        inner.notifications.write().push((component_manager_type_id, notification));
+    }
+}
+
+/// delivers the notifications still queued for an unlocked manager, so it has seen
+/// everything that happened before it is told about anything new
+pub fn flush_component_manager<'a>(scene: &'a Scene, component_manager_type_id: &'a TypeId, component_manager: &'a mut ComponentManager) {
+    if let Some(inner) = unsafe {scene.inner.as_ref()} {
+        deliver_pending(inner, component_manager_type_id, component_manager);
+    }
+}
+
+fn deliver_pending(inner: &SceneInner, component_manager_type_id: &TypeId, component_manager: &mut ComponentManager) {
+    let notifications = mem::replace(&mut *inner.notifications.write(), Vec::new());
+    let mut pending = Vec::new();
+
+    for (type_id, notification) in notifications {
+        if &type_id == component_manager_type_id {
+            notification.deliver(component_manager);
+        } else {
+            pending.push((type_id, notification));
+        }
+    }
+
+    let mut notifications = inner.notifications.write();
+    let queued = mem::replace(&mut *notifications, pending);
+    notifications.extend(queued);
+}
+
+/// hands `component` to the manager of `prev` in its place if the manager holds
+/// `prev`, the component count stays the same so the manager is kept, the manager
+/// must not be locked
+pub fn swap_component<'a>(scene: &'a mut Scene, prev: &'a mut Box<Component>, component: &'a mut Box<Component>) {
+    if let Some(inner) = unsafe {scene.inner.as_mut()} {
+        let component_manager_type_id = prev.component_manager_type_id();
 
-            if is_empty {
-                remove_component_manager(scene, component_manager);
+        if let Some(component_manager) = component_manager_entry(inner, &component_manager_type_id) {
+            let mut component_manager = component_manager.write();
+            deliver_pending(inner, &component_manager_type_id, &mut **component_manager);
+
+            if entity::is_registered(prev) {
+                entity::unregister_component(&mut **component_manager, prev);
+                entity::register_component(&mut **component_manager, component);
             }
         }
+    }
+}
 
-        if is_empty {
-            inner.component_managers.write().remove(&component_manager_type_id);
+pub fn component_manager<'a>(scene: &'a Scene, component_manager_type_id: &'a TypeId) -> Option<Arc<RwLock<Box<ComponentManager>>>> {
+    if let Some(inner) = unsafe {scene.inner.as_ref()} {
+        component_manager_entry(inner, component_manager_type_id)
+    } else {
+        None
+    }
+}
+
+pub fn has_component_manager<'a>(scene: &'a Scene, component_manager_type_id: &'a TypeId) -> bool {
+    if let Some(inner) = unsafe {scene.inner.as_ref()} {
+        inner.component_managers.read().contains_key(component_manager_type_id)
+    } else {
+        false
+    }
+}
+
+pub fn is_component_manager_locked<'a>(scene: &'a Scene, component_manager_type_id: &'a TypeId) -> bool {
+    match component_manager(scene, component_manager_type_id) {
+        Some(component_manager) => component_manager.try_write().is_none(),
+        None => false,
+    }
+}
+
+/// `component` stays on its entity, like when the entity leaves the scene, it is
+/// taken from the manager if the manager holds it or will once its queue is delivered
+pub fn remove_component<'a>(scene: &'a mut Scene, component: &'a mut Box<Component>) -> Result<(), SceneError> {
+    if let Some(inner) = unsafe {scene.inner.as_mut()} {
+        let component_manager_type_id = component.component_manager_type_id();
+
+        match component_manager_entry(inner, &component_manager_type_id) {
+            Some(component_manager) => {
+                match component_manager.try_write() {
+                    Some(mut component_manager) => {
+                        deliver_pending(inner, &component_manager_type_id, &mut **component_manager);
+                        entity::unregister_component(&mut **component_manager, component);
+                    },
+                    None => if let Some(entity) = component.entity() {
+                        let key = entity::component_key(component);
+                        inner.notifications.write().push((component_manager_type_id, Notification::RemoveComponent(entity, key)));
+                    },
+                }
+                release_component(inner, &component_manager_type_id);
+            },
+            None => return Err(SceneError::ComponentManagerNotFound(component_manager_type_id)),
         }
     }
+    Ok(())
 }
 
-impl PartialEq<Scene> for Scene {
-    fn eq(&self, other: &Self) -> bool {
-        match unsafe {self.inner.as_ref()} {
-            Some(a) => match unsafe {other.inner.as_ref()} {
-                Some(b) => a as *const _ == b as *const _,
-                None => false,
+/// `component` was taken off its entity, when its manager is locked the component
+/// is queued and `None` is returned, otherwise it is given back, the manager's
+/// queue is delivered first so it never keeps a component it was handed late
+pub fn remove_boxed_component<'a>(scene: &'a mut Scene, mut component: Box<Component>) -> Result<Option<Box<Component>>, SceneError> {
+    if let Some(inner) = unsafe {scene.inner.as_mut()} {
+        let component_manager_type_id = component.component_manager_type_id();
+
+        match component_manager_entry(inner, &component_manager_type_id) {
+            Some(component_manager) => {
+                let queued = match component_manager.try_write() {
+                    Some(mut component_manager) => {
+                        deliver_pending(inner, &component_manager_type_id, &mut **component_manager);
+                        entity::unregister_component(&mut **component_manager, &mut component);
+                        false
+                    },
+                    None => true,
+                };
+                release_component(inner, &component_manager_type_id);
+
+                if queued {
+                    inner.notifications.write().push((component_manager_type_id, Notification::RemovedComponent(component)));
+                    return Ok(None);
+                }
             },
-            None => false,
+            None => return Err(SceneError::ComponentManagerNotFound(component_manager_type_id)),
+        }
+    }
+    Ok(Some(component))
+}
+
+/// one less component counted for the manager, it is removed when none are left
+/// and it is empty
+fn release_component(inner: &SceneInner, component_manager_type_id: &TypeId) {
+    let count = {
+        let mut component_counts = inner.component_counts.write();
+        let mut count = 0usize;
+
+        if let Some(c) = component_counts.get_mut(component_manager_type_id) {
+            *c -= 1usize;
+            count = *c;
+        }
+        if count == 0usize {
+            component_counts.remove(component_manager_type_id);
+        }
+        count
+    };
+
+    if count == 0usize {
+        remove_empty_component_managers(inner);
+    }
+}
+
+impl Drop for SceneInner {
+    fn drop(&mut self) {
+        if self.initted.load(Ordering::Relaxed) {
+            destroy_component_managers(self);
         }
     }
+}
+
+impl WeakScene {
+    pub fn upgrade(&self) -> Option<Scene> {
+        self.inner.upgrade().map(|inner| Scene {
+            inner: inner,
+        })
+    }
+}
+
+impl PartialEq<Scene> for Scene {
+    fn eq(&self, other: &Self) -> bool {
+        self.inner.ptr_eq(&other.inner)
+    }
     fn ne(&self, other: &Self) -> bool {
         !self.eq(other)
     }
@@ -0,0 +1,59 @@
+use alloc::string::String;
+
+use core::any::TypeId;
+use core::fmt;
+
+use dependency_error::DependencyError;
+use hierarchy_error::HierarchyError;
+
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SceneError {
+    Hierarchy(HierarchyError),
+    Dependency(DependencyError),
+    AlreadyInitted,
+    NotInitted,
+    ComponentAlreadyAdded(TypeId),
+    ComponentNotFound(TypeId),
+    ComponentManagerNotFound(TypeId),
+    ComponentManagerLocked(TypeId),
+    ComponentBorrowed(TypeId),
+    ComponentNotCloneable(TypeId),
+    TagAlreadyAdded(String),
+    TagNotFound(String),
+    IndexOutOfBounds(usize),
+    ComponentIndexOutOfBounds(TypeId, usize),
+}
+
+impl From<HierarchyError> for SceneError {
+    fn from(error: HierarchyError) -> Self {
+        SceneError::Hierarchy(error)
+    }
+}
+
+impl From<DependencyError> for SceneError {
+    fn from(error: DependencyError) -> Self {
+        SceneError::Dependency(error)
+    }
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &SceneError::Hierarchy(ref error) => fmt::Display::fmt(error, f),
+            &SceneError::Dependency(ref error) => fmt::Display::fmt(error, f),
+            &SceneError::AlreadyInitted => write!(f, "scene is already initted"),
+            &SceneError::NotInitted => write!(f, "scene is not initted"),
+            &SceneError::ComponentAlreadyAdded(ref type_id) => write!(f, "entity already has component {:?}", type_id),
+            &SceneError::ComponentNotFound(ref type_id) => write!(f, "entity does not have component {:?}", type_id),
+            &SceneError::ComponentManagerNotFound(ref type_id) => write!(f, "scene does not have component manager {:?}", type_id),
+            &SceneError::ComponentManagerLocked(ref type_id) => write!(f, "component manager {:?} is locked", type_id),
+            &SceneError::ComponentBorrowed(ref type_id) => write!(f, "component {:?} is borrowed", type_id),
+            &SceneError::ComponentNotCloneable(ref type_id) => write!(f, "component {:?} can not be cloned", type_id),
+            &SceneError::TagAlreadyAdded(ref tag) => write!(f, "entity already has tag {:?}", tag),
+            &SceneError::TagNotFound(ref tag) => write!(f, "entity does not have tag {:?}", tag),
+            &SceneError::IndexOutOfBounds(index) => write!(f, "child index {} is out of bounds", index),
+            &SceneError::ComponentIndexOutOfBounds(ref type_id, index) => write!(f, "component {:?} index {} is out of bounds", type_id, index),
+        }
+    }
+}
@@ -0,0 +1,234 @@
+use alloc::boxed::Box;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+use core::any::TypeId;
+use core::fmt;
+
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_value::{self, Value};
+
+use hash_map::HashMap;
+use insert::Insert;
+use map::Map;
+use iterable::Iterable;
+
+use entity::{self, Entity};
+use scene::{self, Scene};
+use scene_error::SceneError;
+use registry_error::RegistryError;
+use component_registry::ComponentRegistry;
+use component::{Component, ComponentBase};
+
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ComponentData {
+    pub name: String,
+    pub value: Value,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EntityData {
+    pub name: String,
+    pub tags: Vec<String>,
+    pub layer_mask: u32,
+    pub active: bool,
+    pub components: Vec<ComponentData>,
+    pub children: Vec<EntityData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SceneData {
+    pub entities: Vec<EntityData>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SerdeError {
+    UnregisteredComponent(TypeId),
+    UnknownComponent(String),
+    Serialize(String),
+    Deserialize(String),
+    Scene(SceneError),
+}
+
+impl From<SceneError> for SerdeError {
+    fn from(error: SceneError) -> Self {
+        SerdeError::Scene(error)
+    }
+}
+
+impl fmt::Display for SerdeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            &SerdeError::UnregisteredComponent(ref type_id) => write!(f, "component {:?} is not registered", type_id),
+            &SerdeError::UnknownComponent(ref name) => write!(f, "no component registered as {:?}", name),
+            &SerdeError::Serialize(ref error) => write!(f, "failed to serialize component: {}", error),
+            &SerdeError::Deserialize(ref error) => write!(f, "failed to deserialize component: {}", error),
+            &SerdeError::Scene(ref error) => write!(f, "{}", error),
+        }
+    }
+}
+
+
+struct Functions {
+    serialize: fn(&Component) -> Result<Value, SerdeError>,
+    deserialize: fn(Value) -> Result<Box<Component>, SerdeError>,
+}
+
+/// adds serialization to a `ComponentRegistry`, which provides the names
+/// written to saved scenes
+pub struct SerdeRegistry {
+    registry: ComponentRegistry,
+    functions: HashMap<TypeId, Functions>,
+}
+
+impl SerdeRegistry {
+    pub fn new() -> Self {
+        Self::with_registry(ComponentRegistry::new())
+    }
+    pub fn with_registry(registry: ComponentRegistry) -> Self {
+        SerdeRegistry {
+            registry: registry,
+            functions: HashMap::new(),
+        }
+    }
+
+    pub fn registry(&self) -> &ComponentRegistry {
+        &self.registry
+    }
+    pub fn registry_mut(&mut self) -> &mut ComponentRegistry {
+        &mut self.registry
+    }
+
+    /// `name` is written to saved scenes in place of the component's type,
+    /// so it must stay the same across builds, types already in the
+    /// `ComponentRegistry` must be registered under the same name
+    pub fn register<T: Component + Serialize + DeserializeOwned>(&mut self, name: &str) -> Result<&mut Self, RegistryError> {
+        let type_id = TypeId::of::<T>();
+
+        if self.functions.contains_key(&type_id) {
+            return Err(RegistryError::DuplicateType(type_id));
+        }
+        match self.registry.type_name(&type_id) {
+            Some(registered) if registered == name => (),
+            Some(_) => return Err(RegistryError::DuplicateType(type_id)),
+            None => {
+                self.registry.register::<T>(name)?;
+            },
+        }
+        self.functions.insert(type_id, Functions {
+            serialize: serialize::<T>,
+            deserialize: deserialize::<T>,
+        });
+
+        Ok(self)
+    }
+    pub fn is_registered<T: Component>(&self) -> bool {
+        self.functions.contains_key(&TypeId::of::<T>())
+    }
+
+    pub fn save_scene(&self, scene: &Scene) -> Result<SceneData, SerdeError> {
+        let mut entities = Vec::new();
+
+        for entity in scene::entities(scene).iter() {
+            if entity.parent().is_none() {
+                entities.push(self.save_entity(entity)?);
+            }
+        }
+        Ok(SceneData {
+            entities: entities,
+        })
+    }
+    pub fn load_scene(&self, data: SceneData) -> Result<Scene, SerdeError> {
+        let mut scene = Scene::new();
+
+        for entity_data in data.entities {
+            let entity = self.load_entity(entity_data)?;
+            scene.add_entity(entity)?;
+        }
+        Ok(scene)
+    }
+
+    pub fn save_entity(&self, entity: &Entity) -> Result<EntityData, SerdeError> {
+        let mut tags = Vec::new();
+        let mut components = Vec::new();
+        let mut children = Vec::new();
+
+        if let Some(entity_tags) = entity::tags(entity) {
+            for tag in entity_tags.iter() {
+                tags.push(tag.clone());
+            }
+        }
+        for id in entity::component_type_ids(entity).iter() {
+            let (name, functions) = match (self.registry.type_name(id), self.functions.get(id)) {
+                (Some(name), Some(functions)) => (name, functions),
+                _ => return Err(SerdeError::UnregisteredComponent(*id)),
+            };
+
+            for index in 0..entity::component_count(entity, id) {
+                if let Some(component) = entity::boxed_component(entity, id, index) {
+                    components.push(ComponentData {
+                        name: String::from(name),
+                        value: (functions.serialize)(&**component)?,
+                    });
+                }
+            }
+        }
+        // components are kept in a hash map, sort them so saved scenes diff cleanly,
+        // the sort is stable so several components of one type keep their order
+        components.sort_by(|a, b| a.name.cmp(&b.name));
+
+        for child in entity.children() {
+            children.push(self.save_entity(&child)?);
+        }
+
+        Ok(EntityData {
+            name: entity.name().to_string(),
+            tags: tags,
+            layer_mask: entity.layer_mask(),
+            active: entity.active_self(),
+            components: components,
+            children: children,
+        })
+    }
+    pub fn load_entity(&self, data: EntityData) -> Result<Entity, SerdeError> {
+        let mut entity = Entity::new();
+
+        entity.set_name(data.name);
+        entity.set_layer_mask(data.layer_mask);
+        entity.set_active(data.active);
+
+        for tag in data.tags.iter() {
+            entity.add_tag(tag);
+        }
+        for component_data in data.components {
+            let functions = match self.registry.type_id(&component_data.name).and_then(|id| self.functions.get(&id)) {
+                Some(functions) => functions,
+                None => return Err(SerdeError::UnknownComponent(component_data.name)),
+            };
+            let component = (functions.deserialize)(component_data.value)?;
+            entity::add_boxed_component(&mut entity, component)?;
+        }
+        for child_data in data.children {
+            let child = self.load_entity(child_data)?;
+            entity.add_child(child)?;
+        }
+        Ok(entity)
+    }
+}
+
+fn serialize<T: Component + Serialize>(component: &Component) -> Result<Value, SerdeError> {
+    match component.downcast_ref::<T>() {
+        Some(component) => serde_value::to_value(component)
+            .map_err(|error| SerdeError::Serialize(error.to_string())),
+        None => Err(SerdeError::UnregisteredComponent(ComponentBase::type_id(component))),
+    }
+}
+
+fn deserialize<T: Component + DeserializeOwned>(value: Value) -> Result<Box<Component>, SerdeError> {
+    match value.deserialize_into::<T>() {
+        Ok(component) => Ok(Box::new(component)),
+        Err(error) => Err(SerdeError::Deserialize(error.to_string())),
+    }
+}
@@ -0,0 +1,147 @@
+#![cfg(feature = "serialize")]
+
+extern crate serde_json;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate scene_graph_derive;
+
+extern crate scene_graph;
+
+
+use std::any::TypeId;
+
+use scene_graph::{
+    Scene, WeakScene, Entity, WeakEntity, Component, ComponentBase, ComponentManager,
+    ComponentRegistry, RegistryError, SceneError, SerdeRegistry, SerdeError, SceneData,
+};
+
+
+#[derive(ComponentManager, Default)]
+pub struct SpriteManager {
+    scene: Option<WeakScene>,
+    components: usize,
+}
+impl ComponentManager for SpriteManager {
+    fn is_empty(&self) -> bool {
+        self.components == 0usize
+    }
+
+    fn add_component(&mut self, _: &mut Box<Component>) {
+        self.components += 1;
+    }
+    fn remove_component(&mut self, _: &mut Box<Component>) {
+        self.components -= 1;
+    }
+}
+
+#[derive(Component, Serialize, Deserialize)]
+#[component_manager(SpriteManager)]
+pub struct Sprite {
+    #[serde(skip)]
+    entity: Option<WeakEntity>,
+    image: String,
+    size: [f32; 2],
+}
+impl Sprite {
+    pub fn new(image: &str, size: [f32; 2]) -> Self {
+        Sprite {
+            entity: None,
+            image: String::from(image),
+            size: size,
+        }
+    }
+    pub fn image(&self) -> &str {
+        &self.image
+    }
+    pub fn size(&self) -> &[f32; 2] {
+        &self.size
+    }
+}
+impl Component for Sprite {}
+
+
+#[test]
+fn test_scene_round_trip() {
+    let mut registry = SerdeRegistry::new();
+    registry.register::<Sprite>("sprite").unwrap();
+
+    let mut scene = Scene::new();
+    let mut player = Entity::new();
+    let mut shadow = Entity::new();
+
+    player.set_name("player").add_tag("player").set_layer_mask(3);
+    player.add_component(Sprite::new("player.png", [32f32, 64f32]));
+    shadow.set_name("shadow").set_active(false);
+    shadow.add_component(Sprite::new("shadow.png", [32f32, 8f32]));
+    player.add_child(shadow.clone()).unwrap();
+    scene.add_entity(player.clone()).unwrap();
+
+    let json = serde_json::to_string(&registry.save_scene(&scene).unwrap()).unwrap();
+    let data: SceneData = serde_json::from_str(&json).unwrap();
+    let loaded = registry.load_scene(data).unwrap();
+
+    let player = loaded.find_path("/player").unwrap();
+    assert!(player.has_tag("player"));
+    assert_eq!(player.layer_mask(), 3);
+    assert_eq!(player.component::<Sprite>().unwrap().image(), "player.png");
+    assert_eq!(player.component::<Sprite>().unwrap().size(), &[32f32, 64f32]);
+    assert!(player.component::<Sprite>().unwrap().entity() == Some(player.clone()));
+    assert!(loaded.has_component_manager::<SpriteManager>());
+
+    let shadow = loaded.find_path("/player/shadow").unwrap();
+    assert!(!shadow.active_self());
+    assert_eq!(shadow.component::<Sprite>().unwrap().image(), "shadow.png");
+    assert_eq!(shadow.component::<Sprite>().unwrap().size(), &[32f32, 8f32]);
+
+    assert_eq!(
+        serde_json::to_string(&registry.save_scene(&loaded).unwrap()).unwrap(),
+        json
+    );
+}
+
+#[test]
+fn test_scene_unregistered_component() {
+    let registry = SerdeRegistry::new();
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Sprite::new("player.png", [32f32, 64f32]));
+    scene.add_entity(entity).unwrap();
+
+    assert_eq!(registry.save_scene(&scene).err(), Some(SerdeError::UnregisteredComponent(TypeId::of::<Sprite>())));
+
+    let json = r#"{"entities":[{"name":"","tags":[],"layer_mask":1,"active":true,"components":[{"name":"sprite","value":{}}],"children":[]}]}"#;
+    let data: SceneData = serde_json::from_str(json).unwrap();
+
+    assert_eq!(registry.load_scene(data).err(), Some(SerdeError::UnknownComponent(String::from("sprite"))));
+}
+
+#[test]
+fn test_scene_load_error() {
+    let mut registry = SerdeRegistry::new();
+    registry.register::<Sprite>("sprite").unwrap();
+
+    let json = r#"{"entities":[{"name":"","tags":[],"layer_mask":1,"active":true,"components":[{"name":"sprite","value":{"image":"a.png","size":[1.0,1.0]}},{"name":"sprite","value":{"image":"b.png","size":[1.0,1.0]}}],"children":[]}]}"#;
+    let data: SceneData = serde_json::from_str(json).unwrap();
+
+    assert_eq!(
+        registry.load_scene(data).err(),
+        Some(SerdeError::Scene(SceneError::ComponentAlreadyAdded(TypeId::of::<Sprite>())))
+    );
+}
+
+#[test]
+fn test_serde_registry_names() {
+    let mut components = ComponentRegistry::new();
+    components.register::<Sprite>("sprite").unwrap();
+
+    let mut registry = SerdeRegistry::with_registry(components);
+    assert!(!registry.is_registered::<Sprite>());
+    assert_eq!(registry.register::<Sprite>("image").err(), Some(RegistryError::DuplicateType(TypeId::of::<Sprite>())));
+
+    registry.register::<Sprite>("sprite").unwrap();
+    assert!(registry.is_registered::<Sprite>());
+    assert_eq!(registry.registry().type_id("sprite"), Some(TypeId::of::<Sprite>()));
+    assert_eq!(registry.register::<Sprite>("sprite").err(), Some(RegistryError::DuplicateType(TypeId::of::<Sprite>())));
+}
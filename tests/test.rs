@@ -1,9 +1,17 @@
 extern crate scene_graph;
+#[macro_use]
+extern crate scene_graph_derive;
 
 
 use std::any::TypeId;
+use std::cell::RefCell;
+use std::rc::Rc;
 
-use scene_graph::{Scene, Entity, Component, ComponentManager};
+use scene_graph::{
+    Scene, WeakScene, Entity, WeakEntity, Component, ComponentBase, ComponentManager,
+    ComponentRegistry, DependencyError, HierarchyError, RegistryError, SceneError, EntityMap, Prefab, With, Without, Changed,
+    DEFAULT_LAYER_MASK,
+};
 
 
 #[test]
@@ -13,18 +21,18 @@ fn test_scene() {
     let entity1 = Entity::new();
     let entity2 = Entity::new();
 
-    entity0.add_child(entity1.clone());
-    entity0.add_child(entity2.clone());
+    entity0.add_child(entity1.clone()).unwrap();
+    entity0.add_child(entity2.clone()).unwrap();
 
-    scene.add_entity(entity0.clone());
+    scene.add_entity(entity0.clone()).unwrap();
 
     assert_eq!(entity0.depth(), 0);
     assert_eq!(entity1.depth(), 1);
     assert_eq!(entity2.depth(), 1);
 
-    assert!(entity0.scene() == Some(&scene));
-    assert!(entity1.scene() == Some(&scene));
-    assert!(entity2.scene() == Some(&scene));
+    assert!(entity0.scene() == Some(scene.clone()));
+    assert!(entity1.scene() == Some(scene.clone()));
+    assert!(entity2.scene() == Some(scene.clone()));
 }
 #[test]
 fn test_scene_remove() {
@@ -33,18 +41,18 @@ fn test_scene_remove() {
     let entity1 = Entity::new();
     let entity2 = Entity::new();
 
-    entity0.add_child(entity1.clone());
-    entity0.add_child(entity2.clone());
+    entity0.add_child(entity1.clone()).unwrap();
+    entity0.add_child(entity2.clone()).unwrap();
 
-    scene.add_entity(entity0.clone());
-    scene.remove_entity(&mut entity0);
+    scene.add_entity(entity0.clone()).unwrap();
+    scene.remove_entity(&mut entity0).unwrap();
 
     assert_eq!(entity0.depth(), 0);
     assert_eq!(entity1.depth(), 1);
     assert_eq!(entity2.depth(), 1);
 
-    assert!(entity1.parent() == Some(&entity0));
-    assert!(entity2.parent() == Some(&entity0));
+    assert!(entity1.parent() == Some(entity0.clone()));
+    assert!(entity2.parent() == Some(entity0.clone()));
 
     assert!(entity0.scene() == None);
     assert!(entity1.scene() == None);
@@ -60,12 +68,12 @@ fn test_entity_depth() {
     let entity4 = Entity::new();
     let entity5 = Entity::new();
 
-    entity0.add_child(entity1.clone());
-    entity0.add_child(entity2.clone());
+    entity0.add_child(entity1.clone()).unwrap();
+    entity0.add_child(entity2.clone()).unwrap();
 
-    entity1.add_child(entity3.clone());
-    entity3.add_child(entity4.clone());
-    entity3.add_child(entity5.clone());
+    entity1.add_child(entity3.clone()).unwrap();
+    entity3.add_child(entity4.clone()).unwrap();
+    entity3.add_child(entity5.clone()).unwrap();
 
     assert_eq!(entity0.depth(), 0);
     assert_eq!(entity1.depth(), 1);
@@ -74,9 +82,9 @@ fn test_entity_depth() {
     assert_eq!(entity4.depth(), 3);
     assert_eq!(entity5.depth(), 3);
 
-    assert!(entity3.parent() == Some(&entity1));
-    assert!(entity4.parent() == Some(&entity3));
-    assert!(entity5.parent() == Some(&entity3));
+    assert!(entity3.parent() == Some(entity1.clone()));
+    assert!(entity4.parent() == Some(entity3.clone()));
+    assert!(entity5.parent() == Some(entity3.clone()));
 }
 #[test]
 fn test_entity_depth_detach() {
@@ -87,18 +95,18 @@ fn test_entity_depth_detach() {
     let entity4 = Entity::new();
     let entity5 = Entity::new();
 
-    entity0.add_child(entity1.clone());
-    entity0.add_child(entity2.clone());
+    entity0.add_child(entity1.clone()).unwrap();
+    entity0.add_child(entity2.clone()).unwrap();
 
-    entity1.add_child(entity3.clone());
-    entity3.add_child(entity4.clone());
-    entity3.add_child(entity5.clone());
+    entity1.add_child(entity3.clone()).unwrap();
+    entity3.add_child(entity4.clone()).unwrap();
+    entity3.add_child(entity5.clone()).unwrap();
 
     entity3.detach();
 
     assert!(entity3.parent() == None);
-    assert!(entity4.parent() == Some(&entity3));
-    assert!(entity5.parent() == Some(&entity3));
+    assert!(entity4.parent() == Some(entity3.clone()));
+    assert!(entity5.parent() == Some(entity3.clone()));
 
     assert_eq!(entity0.depth(), 0);
     assert_eq!(entity1.depth(), 1);
@@ -108,44 +116,49 @@ fn test_entity_depth_detach() {
     assert_eq!(entity5.depth(), 1);
 }
 
+#[derive(ComponentManager, Default)]
 pub struct TransformManager {
-    scene: Option<Scene>,
+    scene: Option<WeakScene>,
     components: usize,
+    initted: bool,
+    events: Vec<&'static str>,
 }
 impl TransformManager {
-    pub fn new() -> Self {
-        TransformManager {
-            scene: None,
-            components: 0usize,
-        }
+    pub fn initted(&self) -> bool {
+        self.initted
+    }
+    pub fn events(&self) -> &[&'static str] {
+        &self.events
     }
 }
 impl ComponentManager for TransformManager {
-    fn type_id(&self) -> TypeId {
-        TypeId::of::<TransformManager>()
+    fn is_empty(&self) -> bool {
+        self.components == 0usize
     }
 
-    fn scene(&self) -> Option<Scene> {
-        self.scene.clone()
+    fn init(&mut self) {
+        self.initted = true;
+        self.events.push("init");
     }
-    fn set_scene(&mut self, scene: Option<Scene>) {
-        self.scene = scene;
-    }
-
-    fn is_empty(&self) -> bool {
-        self.components == 0usize
+    fn destroy(&mut self) {
+        self.initted = false;
+        self.events.push("destroy");
     }
 
     fn add_component(&mut self, _: &mut Box<Component>) {
         self.components += 1;
+        self.events.push("add");
     }
     fn remove_component(&mut self, _: &mut Box<Component>) {
         self.components -= 1;
+        self.events.push("remove");
     }
 }
 
+#[derive(Component)]
+#[component_manager(TransformManager)]
 pub struct Transform {
-    entity: Option<Entity>,
+    entity: Option<WeakEntity>,
     position: [f32; 2]
 }
 impl Transform {
@@ -163,25 +176,118 @@ impl Transform {
     }
 }
 impl Component for Transform {
-    fn type_id(&self) -> TypeId {
-        TypeId::of::<Transform>()
+    fn clone_component(&self) -> Option<Box<Component>> {
+        let mut transform = Transform::new();
+        transform.set_position(self.position);
+        Some(Box::new(transform))
     }
 
-    fn entity(&self) -> Option<Entity> {
-        self.entity.clone()
+    fn prefab_fields(&self) -> &'static [&'static str] {
+        &["x", "y"]
     }
-    fn set_entity(&mut self, entity: Option<Entity>) {
-        self.entity = entity;
+    fn field_eq(&self, field: &str, other: &Component) -> bool {
+        let other = other.downcast_ref::<Transform>().unwrap();
+
+        match field {
+            "x" => self.position[0] == other.position[0],
+            "y" => self.position[1] == other.position[1],
+            _ => true,
+        }
     }
+    fn copy_field(&mut self, field: &str, other: &Component) {
+        let other = other.downcast_ref::<Transform>().unwrap();
 
-    fn new_component_manager(&self) -> Box<ComponentManager> {
-        Box::new(TransformManager::new())
+        match field {
+            "x" => self.position[0] = other.position[0],
+            "y" => self.position[1] = other.position[1],
+            _ => (),
+        }
     }
-    fn component_manager_type_id(&self) -> TypeId {
-        TypeId::of::<TransformManager>()
+}
+
+#[derive(Component)]
+#[component_manager(TransformManager)]
+pub struct Follow {
+    entity: Option<WeakEntity>,
+    target: Option<WeakEntity>,
+}
+impl Follow {
+    pub fn new(target: &Entity) -> Self {
+        Follow {
+            entity: None,
+            target: Some(target.downgrade()),
+        }
+    }
+    pub fn target(&self) -> Option<Entity> {
+        self.target.as_ref().and_then(|target| target.upgrade())
+    }
+}
+impl Component for Follow {
+    fn clone_component(&self) -> Option<Box<Component>> {
+        Some(Box::new(Follow {
+            entity: None,
+            target: self.target.clone(),
+        }))
+    }
+    fn remap_entities(&mut self, entity_map: &EntityMap) {
+        self.target = self.target.as_ref().map(|target| entity_map.remap_weak(target));
+    }
+}
+
+#[derive(Component)]
+#[component_manager = "self::TransformManager"]
+pub struct Collider {
+    entity: Option<WeakEntity>,
+    radius: f32,
+}
+impl Collider {
+    pub fn new(radius: f32) -> Self {
+        Collider {
+            entity: None,
+            radius: radius,
+        }
+    }
+    pub fn radius(&self) -> f32 {
+        self.radius
+    }
+    pub fn set_radius(&mut self, radius: f32) {
+        self.radius = radius;
+    }
+}
+impl Component for Collider {
+    fn allows_multiple(&self) -> bool {
+        true
+    }
+
+    fn clone_component(&self) -> Option<Box<Component>> {
+        Some(Box::new(Collider::new(self.radius)))
+    }
+
+    fn prefab_fields(&self) -> &'static [&'static str] {
+        &["radius"]
+    }
+    fn field_eq(&self, field: &str, other: &Component) -> bool {
+        let other = other.downcast_ref::<Collider>().unwrap();
+
+        match field {
+            "radius" => self.radius == other.radius,
+            _ => true,
+        }
+    }
+    fn copy_field(&mut self, field: &str, other: &Component) {
+        let other = other.downcast_ref::<Collider>().unwrap();
+
+        match field {
+            "radius" => self.radius = other.radius,
+            _ => (),
+        }
     }
 }
 
+fn initted(scene: &Scene) -> bool {
+    scene.with_component_manager::<TransformManager, _, _>(|transform_manager| transform_manager.initted()).unwrap()
+}
+
 #[test]
 fn test_scene_components() {
     let mut scene = Scene::new();
@@ -189,7 +295,7 @@ fn test_scene_components() {
     let mut entity = Entity::new();
     entity.add_component(Transform::new());
 
-    scene.add_entity(entity.clone());
+    scene.add_entity(entity.clone()).unwrap();
 
     assert!(entity.has_component::<Transform>());
     assert!(scene.has_component_manager::<TransformManager>());
@@ -211,8 +317,8 @@ fn test_scene_components_remove() {
     let mut entity = Entity::new();
     entity.add_component(Transform::new());
 
-    scene.add_entity(entity.clone());
-    scene.remove_entity(&mut entity);
+    scene.add_entity(entity.clone()).unwrap();
+    scene.remove_entity(&mut entity).unwrap();
 
     entity.remove_component::<Transform>();
 
@@ -220,10 +326,1519 @@ fn test_scene_components_remove() {
     assert!(!scene.has_component_manager::<TransformManager>());
 }
 #[test]
+fn test_scene_child_before_parent() {
+    let mut scene = Scene::new();
+    let mut parent = Entity::new();
+    let mut child = Entity::new();
+    child.add_component(Transform::new());
+    parent.add_child(child.clone()).unwrap();
+
+    scene.add_entity(child.clone()).unwrap();
+    let id = child.id();
+    scene.add_entity(parent.clone()).unwrap();
+
+    assert_eq!(child.id(), id);
+    assert_eq!(scene.query::<&Transform>().count(), 1);
+    assert_eq!(
+        scene.with_component_manager::<TransformManager, _, _>(|transform_manager| transform_manager.events().to_vec()),
+        Some(vec!["add"])
+    );
+
+    scene.remove_entity(&mut child).unwrap();
+    scene.remove_entity(&mut parent).unwrap();
+
+    assert!(child.scene().is_none());
+    assert!(!scene.has_component_manager::<TransformManager>());
+}
+#[test]
 fn test_scene_init() {
     let mut scene = Scene::new();
     let mut entity = Entity::new();
     entity.add_component(Transform::new());
-    scene.add_entity(entity.clone());
-    scene.init();
+    scene.add_entity(entity.clone()).unwrap();
+
+    assert!(!scene.initted());
+    assert!(!initted(&scene));
+    assert_eq!(scene.update(1f64).err(), Some(SceneError::NotInitted));
+
+    scene.init().unwrap();
+
+    assert!(scene.initted());
+    assert!(initted(&scene));
+}
+#[test]
+fn test_scene_init_new_component_manager() {
+    let mut scene = Scene::new();
+    scene.init().unwrap();
+
+    let mut entity = Entity::new();
+    scene.add_entity(entity.clone()).unwrap();
+    entity.add_component(Transform::new());
+
+    assert!(initted(&scene));
+    assert_eq!(
+        scene.with_component_manager::<TransformManager, _, _>(|transform_manager| transform_manager.events().to_vec()),
+        Some(vec!["init", "add"])
+    );
+}
+#[test]
+fn test_scene_destroy() {
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+    entity.add_component(Transform::new());
+    scene.add_entity(entity.clone()).unwrap();
+    scene.init().unwrap();
+
+    let transform_manager = scene.component_manager::<TransformManager>().unwrap();
+
+    scene.destroy().unwrap();
+
+    assert!(!scene.initted());
+    assert!(!transform_manager.read().downcast_ref::<TransformManager>().unwrap().initted());
+}
+#[test]
+fn test_scene_clear_resets_initted() {
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+    entity.add_component(Transform::new());
+    scene.add_entity(entity.clone()).unwrap();
+    scene.init().unwrap();
+
+    scene.clear();
+    assert!(!scene.initted());
+
+    let mut entity = Entity::new();
+    entity.add_component(Transform::new());
+    scene.add_entity(entity.clone()).unwrap();
+    assert!(!initted(&scene));
+
+    scene.init().unwrap();
+    assert!(initted(&scene));
+    scene.with_component_manager_mut::<TransformManager, _, _>(|transform_manager| transform_manager.initted = false);
+    assert!(!initted(&scene));
+}
+
+
+type Log = Rc<RefCell<Vec<&'static str>>>;
+
+macro_rules! logged_component {
+    ($component:ident, $component_manager:ident, $order:expr, [$($dependency:ident),*]) => (
+        #[derive(ComponentManager)]
+        pub struct $component_manager {
+            scene: Option<WeakScene>,
+            components: usize,
+            log: Log,
+        }
+        impl ComponentManager for $component_manager {
+            fn is_empty(&self) -> bool {
+                self.components == 0usize
+            }
+
+            fn order(&self) -> isize {
+                $order
+            }
+            fn dependencies(&self) -> Vec<TypeId> {
+                vec![$(TypeId::of::<$dependency>()),*]
+            }
+
+            fn init(&mut self) {
+                self.log.borrow_mut().push(concat!("init ", stringify!($component_manager)));
+            }
+            fn destroy(&mut self) {
+                self.log.borrow_mut().push(concat!("destroy ", stringify!($component_manager)));
+            }
+            fn update(&mut self, _: f64) {
+                self.log.borrow_mut().push(concat!("update ", stringify!($component_manager)));
+            }
+
+            fn add_component(&mut self, _: &mut Box<Component>) {
+                self.components += 1;
+            }
+            fn remove_component(&mut self, _: &mut Box<Component>) {
+                self.components -= 1;
+            }
+        }
+
+        pub struct $component {
+            entity: Option<WeakEntity>,
+            log: Log,
+        }
+        impl $component {
+            pub fn new(log: &Log) -> Self {
+                $component {
+                    entity: None,
+                    log: log.clone(),
+                }
+            }
+        }
+        impl ComponentBase for $component {
+            fn type_id(&self) -> TypeId {
+                TypeId::of::<$component>()
+            }
+
+            fn entity(&self) -> Option<Entity> {
+                self.entity.as_ref().and_then(|entity| entity.upgrade())
+            }
+            fn set_entity(&mut self, entity: Option<WeakEntity>) {
+                self.entity = entity;
+            }
+
+            fn new_component_manager(&self) -> Box<ComponentManager> {
+                Box::new($component_manager {
+                    scene: None,
+                    components: 0usize,
+                    log: self.log.clone(),
+                })
+            }
+            fn component_manager_type_id(&self) -> TypeId {
+                TypeId::of::<$component_manager>()
+            }
+        }
+        impl Component for $component {}
+    );
+}
+
+logged_component!(Physics, PhysicsManager, 0, []);
+logged_component!(Camera, CameraManager, 1, []);
+logged_component!(Input, InputManager, -1, []);
+
+logged_component!(Culling, CullingManager, -1, [ViewManager]);
+logged_component!(View, ViewManager, -1, [PositionManager]);
+logged_component!(Position, PositionManager, 0, []);
+
+logged_component!(CycleA, CycleAManager, 0, [CycleBManager]);
+logged_component!(CycleB, CycleBManager, 0, [CycleAManager]);
+
+#[test]
+fn test_scene_update_order() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Camera::new(&log));
+    entity.add_component(Physics::new(&log));
+    entity.add_component(Input::new(&log));
+    scene.add_entity(entity.clone()).unwrap();
+
+    scene.init().unwrap();
+    scene.update(1f64 / 60f64).unwrap();
+    scene.destroy().unwrap();
+
+    assert_eq!(*log.borrow(), vec![
+        "init InputManager", "init PhysicsManager", "init CameraManager",
+        "update InputManager", "update PhysicsManager", "update CameraManager",
+        "destroy CameraManager", "destroy PhysicsManager", "destroy InputManager",
+    ]);
+}
+#[test]
+fn test_scene_update_dependencies() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Culling::new(&log));
+    entity.add_component(View::new(&log));
+    entity.add_component(Position::new(&log));
+    scene.add_entity(entity.clone()).unwrap();
+
+    scene.init().unwrap();
+    scene.update(1f64 / 60f64).unwrap();
+
+    assert_eq!(*log.borrow(), vec![
+        "init PositionManager", "init ViewManager", "init CullingManager",
+        "update PositionManager", "update ViewManager", "update CullingManager",
+    ]);
+}
+#[test]
+fn test_scene_missing_dependency() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(View::new(&log));
+    scene.add_entity(entity.clone()).unwrap();
+
+    let error = scene.init().err();
+
+    assert_eq!(error, Some(SceneError::Dependency(DependencyError::MissingDependency {
+        component_manager: TypeId::of::<ViewManager>(),
+        name: "ViewManager",
+        dependency: TypeId::of::<PositionManager>(),
+    })));
+    assert_eq!(
+        error.unwrap().to_string(),
+        "component manager ViewManager depends on a component manager which is not in the scene"
+    );
+    assert!(!scene.initted());
+    assert!(log.borrow().is_empty());
+}
+#[test]
+fn test_scene_late_missing_dependency() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Camera::new(&log));
+    scene.add_entity(entity.clone()).unwrap();
+    scene.init().unwrap();
+
+    assert_eq!(entity.try_add_component(View::new(&log)).err(), Some(SceneError::Dependency(DependencyError::MissingDependency {
+        component_manager: TypeId::of::<ViewManager>(),
+        name: "ViewManager",
+        dependency: TypeId::of::<PositionManager>(),
+    })));
+    assert!(!entity.has_component::<View>());
+    assert!(!scene.has_component_manager::<ViewManager>());
+
+    entity.add_component(Position::new(&log));
+    entity.add_component(View::new(&log));
+    scene.update(1f64).unwrap();
+
+    assert_eq!(*log.borrow(), vec![
+        "init CameraManager", "init PositionManager", "init ViewManager",
+        "update PositionManager", "update ViewManager", "update CameraManager",
+    ]);
+}
+#[test]
+fn test_scene_dependency_cycle() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(CycleA::new(&log));
+    entity.add_component(CycleB::new(&log));
+    scene.add_entity(entity.clone()).unwrap();
+
+    let error = scene.init().err();
+
+    assert_eq!(error, Some(SceneError::Dependency(DependencyError::Cycle {
+        component_managers: vec![
+            TypeId::of::<CycleAManager>(), TypeId::of::<CycleBManager>(), TypeId::of::<CycleAManager>(),
+        ],
+        names: vec!["CycleAManager", "CycleBManager", "CycleAManager"],
+    })));
+    assert_eq!(
+        error.unwrap().to_string(),
+        "component manager dependency cycle: CycleAManager -> CycleBManager -> CycleAManager"
+    );
+    assert!(log.borrow().is_empty());
+}
+#[test]
+fn test_scene_remove_dependency() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(View::new(&log));
+    entity.add_component(Position::new(&log));
+    scene.add_entity(entity.clone()).unwrap();
+    scene.init().unwrap();
+
+    entity.remove_component::<Position>();
+    assert!(scene.has_component_manager::<PositionManager>());
+    scene.update(1f64).unwrap();
+
+    entity.remove_component::<View>();
+    assert!(!scene.has_component_manager::<ViewManager>());
+    assert!(!scene.has_component_manager::<PositionManager>());
+
+    assert_eq!(*log.borrow(), vec![
+        "init PositionManager", "init ViewManager",
+        "update PositionManager", "update ViewManager",
+        "destroy ViewManager", "destroy PositionManager",
+    ]);
+}
+
+#[derive(ComponentManager)]
+pub struct LifecycleManager {
+    scene: Option<WeakScene>,
+    components: usize,
+    log: Log,
+}
+impl ComponentManager for LifecycleManager {
+    fn is_empty(&self) -> bool {
+        self.components == 0usize
+    }
+
+    fn on_parent_changed(&mut self, _: &Entity, _: Option<&Entity>) {
+        self.log.borrow_mut().push("parent changed");
+    }
+    fn on_child_added(&mut self, _: &Entity, _: &Entity) {
+        self.log.borrow_mut().push("child added");
+    }
+    fn on_child_removed(&mut self, _: &Entity, _: &Entity) {
+        self.log.borrow_mut().push("child removed");
+    }
+    fn on_depth_changed(&mut self, _: &Entity, _: usize) {
+        self.log.borrow_mut().push("depth changed");
+    }
+    fn on_enable(&mut self, _: &Entity) {
+        self.log.borrow_mut().push("enable");
+    }
+    fn on_disable(&mut self, _: &Entity) {
+        self.log.borrow_mut().push("disable");
+    }
+
+    fn add_component(&mut self, _: &mut Box<Component>) {
+        self.components += 1;
+    }
+    fn remove_component(&mut self, _: &mut Box<Component>) {
+        self.components -= 1;
+    }
+}
+
+pub struct Lifecycle {
+    entity: Option<WeakEntity>,
+    log: Log,
+}
+impl Lifecycle {
+    pub fn new(log: &Log) -> Self {
+        Lifecycle {
+            entity: None,
+            log: log.clone(),
+        }
+    }
+}
+impl ComponentBase for Lifecycle {
+    fn type_id(&self) -> TypeId {
+        TypeId::of::<Lifecycle>()
+    }
+
+    fn entity(&self) -> Option<Entity> {
+        self.entity.as_ref().and_then(|entity| entity.upgrade())
+    }
+    fn set_entity(&mut self, entity: Option<WeakEntity>) {
+        self.entity = entity;
+    }
+
+    fn new_component_manager(&self) -> Box<ComponentManager> {
+        Box::new(LifecycleManager {
+            scene: None,
+            components: 0usize,
+            log: self.log.clone(),
+        })
+    }
+    fn component_manager_type_id(&self) -> TypeId {
+        TypeId::of::<LifecycleManager>()
+    }
+}
+impl Component for Lifecycle {
+    fn on_attach(&mut self) {
+        assert!(self.entity.is_some());
+        self.log.borrow_mut().push("attach");
+    }
+    fn on_detach(&mut self) {
+        assert!(self.entity.is_some());
+        self.log.borrow_mut().push("detach");
+    }
+    fn on_scene_enter(&mut self, _: &Scene) {
+        self.log.borrow_mut().push("enter");
+    }
+    fn on_scene_exit(&mut self, _: &Scene) {
+        self.log.borrow_mut().push("exit");
+    }
+}
+
+#[test]
+fn test_component_lifecycle() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut parent = Entity::new();
+    let mut child = Entity::new();
+
+    child.add_component(Lifecycle::new(&log));
+    parent.add_child(child.clone()).unwrap();
+
+    scene.add_entity(parent.clone()).unwrap();
+    scene.remove_entity(&mut parent).unwrap();
+
+    scene.add_entity(child.clone()).unwrap();
+    child.remove_component::<Lifecycle>();
+
+    assert_eq!(*log.borrow(), vec!["attach", "enter", "exit", "enter", "exit", "detach"]);
+}
+#[test]
+fn test_hierarchy_notifications() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut a = Entity::new();
+    let mut b = Entity::new();
+    let mut c = Entity::new();
+
+    a.add_component(Lifecycle::new(&log));
+    c.add_component(Lifecycle::new(&log));
+    scene.add_entity(a.clone()).unwrap();
+    scene.add_entity(b.clone()).unwrap();
+    scene.add_entity(c.clone()).unwrap();
+    log.borrow_mut().clear();
+
+    a.add_child(c.clone()).unwrap();
+    assert_eq!(*log.borrow(), vec!["depth changed", "child added", "parent changed"]);
+    log.borrow_mut().clear();
+
+    b.add_child(c.clone()).unwrap();
+    assert_eq!(*log.borrow(), vec!["child removed", "parent changed"]);
+    log.borrow_mut().clear();
+
+    c.detach();
+    assert_eq!(*log.borrow(), vec!["depth changed", "parent changed"]);
+}
+#[derive(ComponentManager, Default)]
+pub struct DetachManager {
+    scene: Option<WeakScene>,
+    entities: Vec<Entity>,
+    deactivate: bool,
+    replace: bool,
+    log: Vec<&'static str>,
+}
+impl ComponentManager for DetachManager {
+    fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+
+    fn update(&mut self, _: f64) {
+        self.log.push("update");
+
+        for entity in self.entities.clone().iter_mut() {
+            if self.deactivate {
+                entity.set_active(false);
+            }
+            if self.replace {
+                entity.remove_component::<Detach>();
+                entity.add_component(Transform::new());
+            }
+            if !self.deactivate && !self.replace {
+                entity.detach();
+            }
+        }
+    }
+
+    fn on_parent_changed(&mut self, _: &Entity, _: Option<&Entity>) {
+        self.log.push("parent changed");
+    }
+    fn on_depth_changed(&mut self, _: &Entity, _: usize) {
+        self.log.push("depth changed");
+    }
+    fn on_disable(&mut self, _: &Entity) {
+        self.log.push("disable");
+    }
+
+    fn add_component(&mut self, component: &mut Box<Component>) {
+        self.entities.push(component.entity().unwrap());
+    }
+    fn remove_component(&mut self, component: &mut Box<Component>) {
+        let entity = component.entity().unwrap();
+        self.entities.retain(|e| e != &entity);
+    }
+}
+
+#[derive(Component, Default)]
+#[component_manager(DetachManager)]
+pub struct Detach {
+    entity: Option<WeakEntity>,
+}
+impl Component for Detach {}
+
+#[test]
+fn test_hierarchy_notifications_during_update() {
+    let mut scene = Scene::new();
+    let mut parent = Entity::new();
+    let mut child = Entity::new();
+
+    child.add_component(Detach::default());
+    parent.add_child(child.clone()).unwrap();
+    scene.add_entity(parent.clone()).unwrap();
+
+    scene.init().unwrap();
+    scene.update(1f64).unwrap();
+
+    assert!(child.parent().is_none());
+    assert_eq!(parent.child_count(), 0);
+    assert_eq!(
+        scene.with_component_manager::<DetachManager, _, _>(|detach_manager| detach_manager.log.clone()),
+        Some(vec!["update", "depth changed", "parent changed"])
+    );
+}
+#[test]
+fn test_entity_active() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut menu = Entity::new();
+    let mut button = Entity::new();
+    let mut label = Entity::new();
+
+    button.add_component(Lifecycle::new(&log));
+    label.add_component(Lifecycle::new(&log));
+    button.add_child(label.clone()).unwrap();
+    menu.add_child(button.clone()).unwrap();
+    scene.add_entity(menu.clone()).unwrap();
+    log.borrow_mut().clear();
+
+    label.set_active(false);
+    assert!(!label.active_self());
+    assert!(!label.active_in_hierarchy());
+    assert_eq!(*log.borrow(), vec!["disable"]);
+    log.borrow_mut().clear();
+
+    menu.set_active(false);
+    assert!(!menu.active_in_hierarchy());
+    assert!(button.active_self());
+    assert!(!button.active_in_hierarchy());
+    assert_eq!(*log.borrow(), vec!["disable"]);
+    assert_eq!(scene.query::<&Lifecycle>().count(), 0);
+    log.borrow_mut().clear();
+
+    label.set_active(true);
+    assert!(!label.active_in_hierarchy());
+    assert!(log.borrow().is_empty());
+
+    menu.set_active(true);
+    assert!(button.active_in_hierarchy());
+    assert!(label.active_in_hierarchy());
+    assert_eq!(*log.borrow(), vec!["enable", "enable"]);
+    assert_eq!(scene.query::<&Lifecycle>().count(), 2);
+    log.borrow_mut().clear();
+
+    button.set_active(false);
+    label.detach();
+    assert!(label.active_in_hierarchy());
+    assert_eq!(*log.borrow(), vec![
+        "disable", "disable", "depth changed", "enable", "child removed", "parent changed",
+    ]);
+}
+#[test]
+fn test_entity_active_component_managers() {
+    let mut scene = Scene::new();
+    let mut parent = Entity::new();
+    let mut child = Entity::new();
+    let mut hidden = Entity::new();
+
+    parent.add_component(Transform::new());
+    child.add_component(Transform::new());
+    parent.add_child(child.clone()).unwrap();
+    scene.add_entity(parent.clone()).unwrap();
+
+    let components = |scene: &Scene| {
+        scene.with_component_manager::<TransformManager, _, _>(|transform_manager| transform_manager.components)
+    };
+    assert_eq!(components(&scene), Some(2));
+
+    parent.set_active(false);
+    assert_eq!(components(&scene), Some(0));
+
+    child.remove_component::<Transform>();
+    assert_eq!(components(&scene), Some(0));
+
+    parent.set_active(true);
+    assert_eq!(components(&scene), Some(1));
+
+    hidden.set_active(false).add_component(Transform::new());
+    scene.add_entity(hidden.clone()).unwrap();
+    assert_eq!(components(&scene), Some(1));
+
+    parent.remove_component::<Transform>();
+    assert_eq!(components(&scene), Some(0));
+
+    hidden.set_active(true);
+    assert_eq!(components(&scene), Some(1));
+
+    hidden.remove_component::<Transform>();
+    assert!(!scene.has_component_manager::<TransformManager>());
+}
+#[test]
+fn test_entity_active_during_update() {
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Detach::default());
+    scene.add_entity(entity.clone()).unwrap();
+    scene.with_component_manager_mut::<DetachManager, _, _>(|detach_manager| detach_manager.deactivate = true);
+
+    scene.init().unwrap();
+    scene.update(1f64).unwrap();
+
+    assert!(!entity.active_in_hierarchy());
+    assert_eq!(
+        scene.with_component_manager::<DetachManager, _, _>(|detach_manager| (detach_manager.log.clone(), detach_manager.entities.len())),
+        Some((vec!["update", "disable"], 0))
+    );
+}
+#[test]
+fn test_components_changed_during_update() {
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Detach::default());
+    scene.add_entity(entity.clone()).unwrap();
+    scene.with_component_manager_mut::<DetachManager, _, _>(|detach_manager| detach_manager.replace = true);
+
+    scene.init().unwrap();
+    scene.update(1f64).unwrap();
+
+    assert!(!entity.has_component::<Detach>());
+    assert!(!scene.has_component_manager::<DetachManager>());
+    assert!(entity.has_component::<Transform>());
+    assert_eq!(
+        scene.with_component_manager::<TransformManager, _, _>(|transform_manager| transform_manager.events().to_vec()),
+        Some(vec!["init", "add"])
+    );
+}
+#[test]
+fn test_components_changed_after_disable_during_update() {
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Detach::default());
+    scene.add_entity(entity.clone()).unwrap();
+    scene.with_component_manager_mut::<DetachManager, _, _>(|detach_manager| {
+        detach_manager.deactivate = true;
+        detach_manager.replace = true;
+    });
+
+    scene.init().unwrap();
+    scene.update(1f64).unwrap();
+
+    assert!(!entity.active_in_hierarchy());
+    assert!(!entity.has_component::<Detach>());
+    assert!(!scene.has_component_manager::<DetachManager>());
+    assert_eq!(
+        scene.with_component_manager::<TransformManager, _, _>(|transform_manager| transform_manager.events().to_vec()),
+        Some(vec!["init"])
+    );
+}
+
+#[test]
+fn test_entity_path() {
+    let mut scene = Scene::new();
+    let mut root = Entity::new();
+    let mut body = Entity::new();
+    let mut arm = Entity::new();
+    let mut hand = Entity::new();
+
+    root.set_name("root");
+    body.set_name("body");
+    arm.set_name("arm");
+    hand.set_name("hand");
+
+    arm.add_child(hand.clone()).unwrap();
+    body.add_child(arm.clone()).unwrap();
+    root.add_child(body.clone()).unwrap();
+    scene.add_entity(root.clone()).unwrap();
+
+    assert_eq!(hand.name(), "hand");
+    assert_eq!(hand.path(), "/root/body/arm/hand");
+
+    assert!(body.find_child("arm") == Some(arm.clone()));
+    assert!(body.find_child("hand") == None);
+    assert!(root.find_path("body/arm/hand") == Some(hand.clone()));
+    assert!(hand.find_path("../../arm") == Some(arm.clone()));
+    assert!(root.find_path("body/leg") == None);
+
+    assert!(scene.find_path("/root/body") == Some(body.clone()));
+    assert!(scene.find_path("/root") == Some(root.clone()));
+    assert!(scene.find_path("/body") == None);
+}
+
+#[test]
+fn test_scene_tags() {
+    let mut scene = Scene::new();
+    let mut root = Entity::new();
+    let mut enemy0 = Entity::new();
+    let mut enemy1 = Entity::new();
+    let mut group = Entity::new();
+
+    enemy0.add_tag("enemy");
+    enemy1.add_tag("enemy").add_tag("static");
+
+    group.add_child(enemy1.clone()).unwrap();
+    root.add_child(enemy0.clone()).unwrap();
+    root.add_child(group.clone()).unwrap();
+    scene.add_entity(root.clone()).unwrap();
+
+    assert!(enemy1.has_tag("static"));
+    assert!(scene.entities_with_tag("enemy", None) == vec![enemy0.clone(), enemy1.clone()]);
+    assert!(scene.entities_with_tag("enemy", Some(&group)) == vec![enemy1.clone()]);
+    assert!(scene.entities_with_tag("static", None) == vec![enemy1.clone()]);
+
+    enemy1.remove_tag("static");
+    assert!(scene.entities_with_tag("static", None).is_empty());
+
+    scene.remove_entity(&mut root).unwrap();
+    assert!(scene.entities_with_tag("enemy", None).is_empty());
+}
+#[test]
+fn test_scene_layers() {
+    let mut scene = Scene::new();
+    let mut root = Entity::new();
+    let mut ui = Entity::new();
+    let mut world = Entity::new();
+
+    ui.set_layer_mask(0b10);
+    world.set_layer_mask(0b111);
+
+    root.add_child(ui.clone()).unwrap();
+    root.add_child(world.clone()).unwrap();
+    scene.add_entity(root.clone()).unwrap();
+
+    assert!(ui.in_layers(0b110));
+    assert!(!ui.in_layers(0b001));
+
+    assert!(scene.entities_in_layers(0b010, None) == vec![ui.clone(), world.clone()]);
+    assert!(scene.entities_in_layers(0b110, None) == vec![ui.clone(), world.clone()]);
+    assert!(scene.entities_in_layers(0b001, None) == vec![root.clone(), world.clone()]);
+    assert!(scene.entities_in_layers(0b011, Some(&world)) == vec![world.clone()]);
+
+    world.set_layer_mask(0b100);
+    assert!(scene.entities_in_layers(0b010, None) == vec![ui.clone()]);
+    assert!(scene.entities_in_layers(0b100, None) == vec![world.clone()]);
+}
+
+#[test]
+fn test_scene_entity_ids() {
+    let mut scene = Scene::new();
+    let mut entity0 = Entity::new();
+    let entity1 = Entity::new();
+
+    assert_eq!(entity0.id(), None);
+
+    entity0.add_child(entity1.clone()).unwrap();
+    scene.add_entity(entity0.clone()).unwrap();
+
+    let id0 = entity0.id().unwrap();
+    let id1 = entity1.id().unwrap();
+
+    assert!(id0 != id1);
+    assert!(scene.get(id0) == Some(entity0.clone()));
+    assert!(scene.get(id1) == Some(entity1.clone()));
+
+    scene.remove_entity(&mut entity0).unwrap();
+
+    assert_eq!(entity0.id(), None);
+    assert!(!scene.contains(id0));
+    assert!(!scene.contains(id1));
+
+    let entity2 = Entity::new();
+    scene.add_entity(entity2.clone()).unwrap();
+
+    let id2 = entity2.id().unwrap();
+
+    assert!(id2.index() == id0.index() || id2.index() == id1.index());
+    assert!(id2 != id0 && id2 != id1);
+    assert!(scene.get(id0) == None);
+    assert!(scene.get(id2) == Some(entity2.clone()));
+}
+
+#[test]
+fn test_entity_destroy() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut root = Entity::new();
+    let mut body = Entity::new();
+    let mut arm = Entity::new();
+
+    body.set_name("body");
+    arm.add_component(Lifecycle::new(&log));
+    body.add_component(Transform::new());
+    body.add_child(arm.clone()).unwrap();
+    root.add_child(body.clone()).unwrap();
+    scene.add_entity(root.clone()).unwrap();
+
+    let body_id = body.id().unwrap();
+    let arm_id = arm.id().unwrap();
+
+    body.destroy().unwrap();
+
+    assert!(root.find_child("body") == None);
+    assert!(!scene.contains(body_id));
+    assert!(!scene.contains(arm_id));
+    assert!(!scene.has_component_manager::<TransformManager>());
+    assert!(!scene.has_component_manager::<LifecycleManager>());
+    assert_eq!(*log.borrow(), vec!["attach", "enter", "exit", "detach"]);
+}
+
+#[test]
+fn test_entity_drop() {
+    let child = {
+        let mut root = Entity::new();
+        let child = Entity::new();
+        root.add_child(child.clone()).unwrap();
+        child.downgrade()
+    };
+    assert!(child.upgrade().is_none());
+
+    let mut root = Entity::new();
+    let weak_root = root.downgrade();
+    let child = Entity::new();
+    root.add_child(child.clone()).unwrap();
+
+    drop(root);
+
+    assert!(weak_root.upgrade().is_none());
+    assert!(child.parent() == None);
+}
+#[test]
+fn test_scene_drop() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut entity = Entity::new();
+    let weak_scene = {
+        let mut scene = Scene::new();
+        entity.add_component(Physics::new(&log));
+        scene.add_entity(entity.clone()).unwrap();
+        scene.init().unwrap();
+        scene.downgrade()
+    };
+
+    assert!(weak_scene.upgrade().is_none());
+    assert!(entity.scene() == None);
+    assert_eq!(*log.borrow(), vec!["init PhysicsManager", "destroy PhysicsManager"]);
+}
+
+#[test]
+fn test_scene_query() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut entity0 = Entity::new();
+    let mut entity1 = Entity::new();
+    let mut entity2 = Entity::new();
+
+    entity0.add_component(Transform::new()).add_component(Physics::new(&log));
+    entity1.add_component(Transform::new());
+    entity2.add_component(Physics::new(&log));
+
+    scene.add_entity(entity0.clone()).unwrap();
+    scene.add_entity(entity1.clone()).unwrap();
+    scene.add_entity(entity2.clone()).unwrap();
+
+    for (_, _, mut transform) in scene.query::<(&Physics, &mut Transform)>() {
+        transform.set_position([1f32; 2]);
+    }
+
+    let entities: Vec<Entity> = scene.query::<&Transform>().map(|(entity, _)| entity).collect();
+    assert!(entities == vec![entity0.clone(), entity1.clone()]);
+
+    assert_eq!(entity0.component::<Transform>().unwrap().position(), &[1f32; 2]);
+    assert_eq!(entity1.component::<Transform>().unwrap().position(), &[0f32; 2]);
+    assert_eq!(scene.query::<(&Physics, &Transform)>().count(), 1);
+}
+
+#[test]
+#[should_panic]
+fn test_scene_query_aliasing_mut() {
+    let scene = Scene::new();
+    let _ = scene.query::<(&mut Transform, &mut Transform)>();
+}
+#[test]
+#[should_panic]
+fn test_scene_query_aliasing_ref_mut() {
+    let scene = Scene::new();
+    let _ = scene.query::<(&Transform, Option<&mut Transform>)>();
+}
+#[test]
+#[should_panic]
+fn test_scene_query_aliasing_queries() {
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Transform::new());
+    scene.add_entity(entity.clone()).unwrap();
+
+    let _rows: Vec<_> = scene.query::<&mut Transform>().collect();
+    let _ = scene.query::<&mut Transform>().next();
+}
+#[test]
+#[should_panic]
+fn test_scene_query_aliasing_component_mut() {
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Transform::new());
+    scene.add_entity(entity.clone()).unwrap();
+
+    let _row = scene.query::<&Transform>().next();
+    let _ = entity.component_mut::<Transform>();
+}
+#[test]
+fn test_scene_query_borrowed_components() {
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Transform::new());
+    scene.add_entity(entity.clone()).unwrap();
+
+    for (mut entity, transform) in scene.query::<&Transform>() {
+        assert_eq!(entity.try_remove_component::<Transform>().err(), Some(SceneError::ComponentBorrowed(TypeId::of::<Transform>())));
+        assert_eq!(entity.try_take_component::<Transform>().err(), Some(SceneError::ComponentBorrowed(TypeId::of::<Transform>())));
+        assert!(entity.replace_component(Transform::new()).is_err());
+        assert_eq!(transform.position(), &[0f32; 2]);
+        assert_eq!(entity.component::<Transform>().unwrap().position(), &[0f32; 2]);
+    }
+
+    entity.try_remove_component::<Transform>().unwrap();
+    assert!(!entity.has_component::<Transform>());
+}
+
+#[test]
+fn test_scene_query_filters() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut root = Entity::new();
+    let mut group = Entity::new();
+    let mut entity0 = Entity::new();
+    let mut entity1 = Entity::new();
+    let mut entity2 = Entity::new();
+
+    entity0.add_component(Transform::new()).add_component(Physics::new(&log));
+    entity1.add_component(Transform::new());
+    entity2.add_component(Transform::new()).add_component(Camera::new(&log));
+
+    group.add_child(entity1.clone()).unwrap();
+    group.add_child(entity2.clone()).unwrap();
+    root.add_child(entity0.clone()).unwrap();
+    root.add_child(group.clone()).unwrap();
+    scene.add_entity(root.clone()).unwrap();
+
+    let without_physics: Vec<Entity> = scene.query_filtered::<&Transform, Without<Physics>>()
+        .map(|(entity, _)| entity)
+        .collect();
+    assert!(without_physics == vec![entity1.clone(), entity2.clone()]);
+
+    let with_camera: Vec<Entity> = scene.query_filtered::<&Transform, (With<Camera>, Without<Physics>)>()
+        .map(|(entity, _)| entity)
+        .collect();
+    assert!(with_camera == vec![entity2.clone()]);
+
+    let optional: Vec<bool> = scene.query::<(&Transform, Option<&Physics>)>()
+        .map(|(_, _, physics)| physics.is_some())
+        .collect();
+    assert_eq!(optional, vec![true, false, false]);
+
+    let within: Vec<Entity> = scene.query::<&Transform>()
+        .within(&group)
+        .map(|(entity, _)| entity)
+        .collect();
+    assert!(within == vec![entity1.clone(), entity2.clone()]);
+
+    assert_eq!(scene.query_filtered::<&Transform, Changed<Transform>>().count(), 3);
+    scene.init().unwrap();
+    scene.update(1f64).unwrap();
+    assert_eq!(scene.query_filtered::<&Transform, Changed<Transform>>().count(), 0);
+
+    for (entity, mut transform) in scene.query::<&mut Transform>() {
+        if entity == entity2 {
+            transform.set_position([2f32; 2]);
+        }
+    }
+    entity1.component_mut::<Transform>().unwrap().set_position([1f32; 2]);
+
+    let changed: Vec<Entity> = scene.query_filtered::<&Transform, Changed<Transform>>()
+        .map(|(entity, _)| entity)
+        .collect();
+    assert!(changed == vec![entity1.clone(), entity2.clone()]);
+}
+
+#[derive(ComponentManager, Default)]
+pub struct ChangeReaderManager {
+    scene: Option<WeakScene>,
+    components: usize,
+    changed: Vec<usize>,
+}
+impl ComponentManager for ChangeReaderManager {
+    fn is_empty(&self) -> bool {
+        self.components == 0usize
+    }
+    fn order(&self) -> isize {
+        -1
+    }
+
+    fn update(&mut self, _: f64) {
+        if let Some(scene) = self.scene() {
+            self.changed.push(scene.query_filtered::<&Transform, Changed<Transform>>().count());
+        }
+    }
+
+    fn add_component(&mut self, _: &mut Box<Component>) {
+        self.components += 1;
+    }
+    fn remove_component(&mut self, _: &mut Box<Component>) {
+        self.components -= 1;
+    }
+}
+
+#[derive(Component, Default)]
+#[component_manager(ChangeReaderManager)]
+pub struct ChangeReader {
+    entity: Option<WeakEntity>,
+}
+impl Component for ChangeReader {}
+
+#[derive(ComponentManager, Default)]
+pub struct ChangeWriterManager {
+    scene: Option<WeakScene>,
+    entities: Vec<Entity>,
+    write: bool,
+}
+impl ComponentManager for ChangeWriterManager {
+    fn is_empty(&self) -> bool {
+        self.entities.is_empty()
+    }
+    fn order(&self) -> isize {
+        1
+    }
+
+    fn update(&mut self, _: f64) {
+        if self.write {
+            for entity in self.entities.iter() {
+                entity.component_mut::<Transform>().unwrap().set_position([1f32; 2]);
+            }
+        }
+    }
+
+    fn add_component(&mut self, component: &mut Box<Component>) {
+        self.entities.push(component.entity().unwrap());
+    }
+    fn remove_component(&mut self, component: &mut Box<Component>) {
+        let entity = component.entity().unwrap();
+        self.entities.retain(|e| e != &entity);
+    }
+}
+
+#[derive(Component, Default)]
+#[component_manager(ChangeWriterManager)]
+pub struct ChangeWriter {
+    entity: Option<WeakEntity>,
+}
+impl Component for ChangeWriter {}
+
+#[test]
+fn test_scene_query_changed_between_managers() {
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+
+    entity.add_component(Transform::new());
+    entity.add_component(ChangeReader::default());
+    entity.add_component(ChangeWriter::default());
+    scene.add_entity(entity.clone()).unwrap();
+    scene.with_component_manager_mut::<ChangeWriterManager, _, _>(|writer| writer.write = true);
+
+    scene.init().unwrap();
+    scene.update(1f64).unwrap();
+    scene.update(1f64).unwrap();
+    scene.with_component_manager_mut::<ChangeWriterManager, _, _>(|writer| writer.write = false);
+    scene.update(1f64).unwrap();
+    scene.update(1f64).unwrap();
+
+    assert_eq!(
+        scene.with_component_manager::<ChangeReaderManager, _, _>(|reader| reader.changed.clone()),
+        Some(vec![1, 1, 1, 0])
+    );
+}
+
+#[test]
+fn test_entity_child_order() {
+    let mut scene = Scene::new();
+    let mut root = Entity::new();
+    let mut a = Entity::new();
+    let mut b = Entity::new();
+    let c = Entity::new();
+    let mut d = Entity::new();
+    let mut other = Entity::new();
+
+    root.add_child(a.clone()).unwrap();
+    root.add_child(c.clone()).unwrap();
+    root.insert_child(1, b.clone()).unwrap();
+    other.add_child(d.clone()).unwrap();
+    scene.add_entity(root.clone()).unwrap();
+
+    assert_eq!(a.sibling_index(), Some(0));
+    assert_eq!(b.sibling_index(), Some(1));
+    assert_eq!(c.sibling_index(), Some(2));
+    assert_eq!(root.sibling_index(), None);
+
+    a.set_sibling_index(2);
+    assert_eq!((b.sibling_index(), c.sibling_index(), a.sibling_index()), (Some(0), Some(1), Some(2)));
+
+    b.move_after(&a).unwrap();
+    assert_eq!((c.sibling_index(), a.sibling_index(), b.sibling_index()), (Some(0), Some(1), Some(2)));
+
+    a.move_before(&c).unwrap();
+    assert_eq!((a.sibling_index(), c.sibling_index(), b.sibling_index()), (Some(0), Some(1), Some(2)));
+
+    root.swap_children(0, 2);
+    assert_eq!((b.sibling_index(), c.sibling_index(), a.sibling_index()), (Some(0), Some(1), Some(2)));
+
+    d.move_after(&b).unwrap();
+    assert!(d.parent() == Some(root.clone()));
+    assert!(d.scene() == Some(scene.clone()));
+    assert_eq!(d.depth(), 1);
+    assert_eq!(d.sibling_index(), Some(1));
+    assert_eq!(c.sibling_index(), Some(2));
+    assert_eq!(other.child_count(), 0);
+}
+
+#[test]
+fn test_entity_set_parent() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene0 = Scene::new();
+    let mut scene1 = Scene::new();
+    let mut parent0 = Entity::new();
+    let mut parent1 = Entity::new();
+    let parent2 = Entity::new();
+    let mut child = Entity::new();
+    let grandchild = Entity::new();
+
+    child.add_component(Lifecycle::new(&log));
+    child.add_child(grandchild.clone()).unwrap();
+    parent0.add_child(child.clone()).unwrap();
+    scene0.add_entity(parent0.clone()).unwrap();
+    scene0.add_entity(parent1.clone()).unwrap();
+    scene1.add_entity(parent2.clone()).unwrap();
+
+    parent1.add_child(child.clone()).unwrap();
+
+    assert_eq!(parent0.child_count(), 0);
+    assert!(child.parent() == Some(parent1.clone()));
+    assert!(child.scene() == Some(scene0.clone()));
+    assert_eq!(grandchild.depth(), 2);
+    assert_eq!(scene0.query::<&Lifecycle>().count(), 1);
+    assert_eq!(*log.borrow(), vec!["attach", "enter"]);
+
+    child.set_parent(Some(&parent2)).unwrap();
+
+    assert_eq!(parent1.child_count(), 0);
+    assert!(child.scene() == Some(scene1.clone()));
+    assert!(grandchild.scene() == Some(scene1.clone()));
+    assert_eq!(scene0.query::<&Lifecycle>().count(), 0);
+    assert_eq!(scene1.query::<&Lifecycle>().count(), 1);
+    assert_eq!(*log.borrow(), vec!["attach", "enter", "exit", "enter"]);
+
+    child.set_parent(None).unwrap();
+
+    assert!(child.parent() == None);
+    assert!(child.scene() == Some(scene1.clone()));
+    assert_eq!(child.depth(), 0);
+    assert_eq!(grandchild.depth(), 1);
+    assert_eq!(*log.borrow(), vec!["attach", "enter", "exit", "enter"]);
+}
+
+#[test]
+fn test_entity_hierarchy_errors() {
+    let mut scene0 = Scene::new();
+    let mut scene1 = Scene::new();
+    let mut root = Entity::new();
+    let mut child = Entity::new();
+    let mut grandchild = Entity::new();
+    let mut other = Entity::new();
+
+    root.add_child(child.clone()).unwrap();
+    child.add_child(grandchild.clone()).unwrap();
+
+    assert_eq!(root.add_child(root.clone()).err(), Some(SceneError::Hierarchy(HierarchyError::WouldCreateCycle)));
+    assert_eq!(grandchild.add_child(root.clone()).err(), Some(SceneError::Hierarchy(HierarchyError::WouldCreateCycle)));
+    assert_eq!(child.set_parent(Some(&grandchild)).err(), Some(SceneError::Hierarchy(HierarchyError::WouldCreateCycle)));
+    assert_eq!(root.add_child(child.clone()).err(), Some(SceneError::Hierarchy(HierarchyError::AlreadyChild)));
+    assert_eq!(root.remove_child(&mut grandchild).err(), Some(SceneError::Hierarchy(HierarchyError::NotAChild)));
+    assert_eq!(other.move_before(&root).err(), Some(SceneError::Hierarchy(HierarchyError::NotAChild)));
+
+    assert_eq!(grandchild.depth(), 2);
+    assert!(grandchild.parent() == Some(child.clone()));
+
+    scene0.add_entity(root.clone()).unwrap();
+    scene0.add_entity(root.clone()).unwrap();
+    assert_eq!(scene1.add_entity(child.clone()).err(), Some(SceneError::Hierarchy(HierarchyError::DifferentScene)));
+    assert_eq!(scene1.remove_entity(&mut root).err(), Some(SceneError::Hierarchy(HierarchyError::DifferentScene)));
+    assert_eq!(scene0.entities_in_layers(DEFAULT_LAYER_MASK, None).len(), 3);
+
+    root.remove_child(&mut child).unwrap();
+    assert!(child.parent() == None);
+    assert!(child.scene() == None);
+    assert!(grandchild.scene() == None);
+}
+
+#[test]
+fn test_entity_traversal() {
+    let mut a = Entity::new();
+    let mut b = Entity::new();
+    let c = Entity::new();
+    let d = Entity::new();
+    let mut e = Entity::new();
+    let f = Entity::new();
+
+    // a
+    // ├── b
+    // │   ├── d
+    // │   └── e
+    // │       └── f
+    // └── c
+    e.add_child(f.clone()).unwrap();
+    b.add_child(d.clone()).unwrap();
+    b.add_child(e.clone()).unwrap();
+    a.add_child(b.clone()).unwrap();
+    a.add_child(c.clone()).unwrap();
+
+    assert_eq!(a.child_count(), 2);
+    assert_eq!(f.child_count(), 0);
+    assert!(a.children().collect::<Vec<_>>() == vec![b.clone(), c.clone()]);
+    assert!(d.siblings().collect::<Vec<_>>() == vec![e.clone()]);
+    assert!(a.siblings().next() == None);
+    assert!(f.ancestors().collect::<Vec<_>>() == vec![e.clone(), b.clone(), a.clone()]);
+    assert!(f.root() == a);
+    assert!(a.root() == a);
+
+    assert!(a.descendants().collect::<Vec<_>>() == vec![
+        b.clone(), d.clone(), e.clone(), f.clone(), c.clone(),
+    ]);
+    assert!(a.descendants_breadth_first().collect::<Vec<_>>() == vec![
+        b.clone(), c.clone(), d.clone(), e.clone(), f.clone(),
+    ]);
+    assert!(e.descendants().collect::<Vec<_>>() == vec![f.clone()]);
+
+    assert!(a.is_ancestor_of(&f));
+    assert!(b.is_ancestor_of(&e));
+    assert!(!c.is_ancestor_of(&f));
+    assert!(!a.is_ancestor_of(&a));
+    assert!(!f.is_ancestor_of(&a));
+}
+#[test]
+fn test_scene_errors() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut parent = Entity::new();
+    let mut child = Entity::new();
+
+    assert_eq!(scene.destroy().err(), Some(SceneError::NotInitted));
+    scene.init().unwrap();
+    assert_eq!(scene.init().err(), Some(SceneError::AlreadyInitted));
+
+    parent.add_child(child.clone()).unwrap();
+    scene.add_entity(parent.clone()).unwrap();
+
+    parent.try_add_component(Physics::new(&log)).unwrap();
+    assert_eq!(parent.try_add_component(Physics::new(&log)).err(), Some(SceneError::ComponentAlreadyAdded(TypeId::of::<Physics>())));
+    parent.try_remove_component::<Physics>().unwrap();
+    assert_eq!(parent.try_remove_component::<Physics>().err(), Some(SceneError::ComponentNotFound(TypeId::of::<Physics>())));
+    assert_eq!(scene.try_component_manager::<PhysicsManager>().err(), Some(SceneError::ComponentManagerNotFound(TypeId::of::<PhysicsManager>())));
+
+    parent.try_add_tag("player").unwrap();
+    assert_eq!(parent.try_add_tag("player").err(), Some(SceneError::TagAlreadyAdded(String::from("player"))));
+    parent.try_remove_tag("player").unwrap();
+    assert_eq!(parent.try_remove_tag("player").err(), Some(SceneError::TagNotFound(String::from("player"))));
+
+    assert_eq!(parent.try_swap_children(0, 1).err(), Some(SceneError::IndexOutOfBounds(1)));
+    assert_eq!(child.try_set_sibling_index(1).err(), Some(SceneError::IndexOutOfBounds(1)));
+    assert_eq!(parent.try_detach().err(), Some(SceneError::Hierarchy(HierarchyError::NotAChild)));
+    child.try_detach().unwrap();
+    assert_eq!(parent.child_count(), 0);
+}
+#[test]
+fn test_entity_instantiate() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut player = Entity::new();
+    let mut weapon = Entity::new();
+    let other = Entity::new();
+
+    player.set_name("player").add_tag("player");
+    weapon.set_name("weapon");
+    player.add_component(Transform::new());
+    player.add_component(Lifecycle::new(&log));
+    weapon.add_component(Follow::new(&player));
+    player.add_child(weapon.clone()).unwrap();
+
+    let mut transform = Transform::new();
+    transform.set_position([1f32, 2f32]);
+    weapon.add_component(transform);
+
+    let mut spawner = Entity::new();
+    spawner.add_component(Follow::new(&other));
+
+    assert_eq!(
+        player.instantiate(Some(&mut scene)).err(),
+        Some(SceneError::ComponentNotCloneable(TypeId::of::<Lifecycle>()))
+    );
+    assert_eq!(scene.query::<&Transform>().count(), 0);
+    player.remove_component::<Lifecycle>();
+
+    let copy = player.instantiate(Some(&mut scene)).unwrap();
+    assert!(copy != player);
+    assert_eq!(copy.name(), "player");
+    assert!(copy.has_tag("player"));
+    assert!(copy.scene() == Some(scene.clone()));
+    assert!(player.scene().is_none());
+    assert!(copy.has_component::<Transform>());
+    assert!(!copy.has_component::<Lifecycle>());
+
+    let weapon_copy = copy.find_child("weapon").unwrap();
+    assert!(weapon_copy != weapon);
+    assert_eq!(weapon_copy.component::<Transform>().unwrap().position(), &[1f32, 2f32]);
+    assert!(weapon_copy.component::<Follow>().unwrap().target() == Some(copy.clone()));
+    assert!(weapon.component::<Follow>().unwrap().target() == Some(player.clone()));
+
+    let spawned = spawner.instantiate(None).unwrap();
+    assert!(spawned.component::<Follow>().unwrap().target() == Some(other.clone()));
+    assert!(spawned.scene().is_none());
+}
+#[test]
+fn test_prefab() {
+    let mut scene = Scene::new();
+    let mut root = Entity::new();
+    root.add_component(Transform::new());
+
+    let mut prefab = Prefab::new(root);
+    let mut a = prefab.instantiate(Some(&mut scene)).unwrap();
+    let b = prefab.instantiate(Some(&mut scene)).unwrap();
+    assert_eq!(prefab.instances().len(), 2);
+
+    a.component_mut::<Transform>().unwrap().set_position([5f32, 0f32]);
+    prefab.edit(|root| root.component_mut::<Transform>().unwrap().set_position([1f32, 2f32]));
+
+    assert!(prefab.is_overridden::<Transform>(&a, "x"));
+    assert!(!prefab.is_overridden::<Transform>(&a, "y"));
+    assert_eq!(a.component::<Transform>().unwrap().position(), &[5f32, 2f32]);
+    assert_eq!(b.component::<Transform>().unwrap().position(), &[1f32, 2f32]);
+
+    prefab.override_field::<Transform>(&b, "y");
+    prefab.edit(|root| root.component_mut::<Transform>().unwrap().set_position([3f32, 4f32]));
+    assert_eq!(a.component::<Transform>().unwrap().position(), &[5f32, 4f32]);
+    assert_eq!(b.component::<Transform>().unwrap().position(), &[3f32, 2f32]);
+
+    b.component_mut::<Transform>().unwrap().set_position([7f32, 2f32]);
+    prefab.revert_field::<Transform>(&a, "x");
+    assert_eq!(a.component::<Transform>().unwrap().position(), &[3f32, 4f32]);
+    assert_eq!(b.component::<Transform>().unwrap().position(), &[7f32, 2f32]);
+
+    a.destroy().unwrap();
+    scene.clear();
+    drop(b);
+    prefab.apply();
+    assert_eq!(prefab.instances().len(), 0);
+}
+#[test]
+fn test_prefab_multiple_components() {
+    let mut root = Entity::new();
+    root.add_component(Collider::new(1f32)).add_component(Collider::new(2f32));
+
+    let mut prefab = Prefab::new(root);
+    let instance = prefab.instantiate(None).unwrap();
+
+    prefab.override_field_at::<Collider>(&instance, 1, "radius");
+    assert!(!prefab.is_overridden::<Collider>(&instance, "radius"));
+    assert!(prefab.is_overridden_at::<Collider>(&instance, 1, "radius"));
+
+    prefab.edit(|root| {
+        root.component_at_mut::<Collider>(0).unwrap().set_radius(3f32);
+        root.component_at_mut::<Collider>(1).unwrap().set_radius(4f32);
+    });
+    assert_eq!(instance.component_at::<Collider>(0).unwrap().radius(), 3f32);
+    assert_eq!(instance.component_at::<Collider>(1).unwrap().radius(), 2f32);
+
+    prefab.revert_field_at::<Collider>(&instance, 1, "radius");
+    assert!(!prefab.is_overridden_at::<Collider>(&instance, 1, "radius"));
+    assert_eq!(instance.component_at::<Collider>(1).unwrap().radius(), 4f32);
+}
+#[test]
+fn test_component_registry() {
+    let mut registry = ComponentRegistry::new();
+    registry
+        .register_with("transform", Transform::new).unwrap()
+        .register::<Lifecycle>("lifecycle").unwrap()
+        .register_component_manager::<TransformManager>("transform_manager").unwrap();
+
+    assert_eq!(registry.register::<Transform>("position").err(), Some(RegistryError::DuplicateType(TypeId::of::<Transform>())));
+    assert_eq!(registry.register::<Physics>("transform").err(), Some(RegistryError::DuplicateName(String::from("transform"))));
+
+    assert_eq!(registry.type_name(&TypeId::of::<Transform>()), Some("transform"));
+    assert_eq!(registry.type_name(&TypeId::of::<TransformManager>()), Some("transform_manager"));
+    assert_eq!(registry.type_name(&TypeId::of::<Physics>()), None);
+    assert_eq!(registry.type_id("lifecycle"), Some(TypeId::of::<Lifecycle>()));
+    assert!(registry.is_registered("transform"));
+    assert!(!registry.is_registered("physics"));
+
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+    scene.add_entity(entity.clone()).unwrap();
+
+    entity.try_add_boxed_component(registry.create("transform").unwrap()).unwrap();
+    assert!(entity.has_component::<Transform>());
+    assert!(scene.has_component_manager::<TransformManager>());
+    assert!(registry.create("lifecycle").is_none());
+    assert!(registry.create("physics").is_none());
+}
+#[test]
+fn test_multiple_components() {
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+    scene.add_entity(entity.clone()).unwrap();
+
+    entity.add_component(Transform::new());
+    assert_eq!(entity.try_add_component(Transform::new()).err(), Some(SceneError::ComponentAlreadyAdded(TypeId::of::<Transform>())));
+    assert_eq!(entity.component_count::<Transform>(), 1);
+
+    entity.add_component(Collider::new(1f32));
+    entity.add_component(Collider::new(2f32));
+    entity.add_component(Collider::new(3f32));
+    assert_eq!(entity.component_count::<Collider>(), 3);
+    assert_eq!(entity.component::<Collider>().unwrap().radius(), 1f32);
+    assert_eq!(entity.component_at::<Collider>(2).unwrap().radius(), 3f32);
+    assert!(entity.component_at::<Collider>(3).is_none());
+    assert_eq!(entity.components::<Collider>().iter().map(|c| c.radius()).collect::<Vec<_>>(), vec![1f32, 2f32, 3f32]);
+
+    entity.try_remove_component_at::<Collider>(1).unwrap();
+    assert_eq!(entity.components::<Collider>().iter().map(|c| c.radius()).collect::<Vec<_>>(), vec![1f32, 3f32]);
+    assert_eq!(entity.try_remove_component_at::<Collider>(2).err(), Some(SceneError::ComponentIndexOutOfBounds(TypeId::of::<Collider>(), 2)));
+
+    entity.remove_component::<Transform>();
+    entity.remove_component::<Collider>();
+    assert!(!entity.has_component::<Collider>());
+    assert!(!scene.has_component_manager::<TransformManager>());
+}
+#[test]
+fn test_component_take_and_replace() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut a = Entity::new();
+    let mut b = Entity::new();
+    scene.add_entity(a.clone()).unwrap();
+    scene.add_entity(b.clone()).unwrap();
+
+    let mut transform = Transform::new();
+    transform.set_position([1f32, 1f32]);
+    assert!(a.insert_component(transform).is_none());
+
+    let mut transform = Transform::new();
+    transform.set_position([2f32, 2f32]);
+    let prev = a.insert_component(transform).unwrap();
+    assert_eq!(prev.position(), &[1f32, 1f32]);
+    assert!(prev.entity().is_none());
+    assert_eq!(a.component::<Transform>().unwrap().position(), &[2f32, 2f32]);
+
+    let prev = a.replace_component(Transform::new()).ok().unwrap();
+    assert_eq!(prev.position(), &[2f32, 2f32]);
+    assert!(b.replace_component(Transform::new()).is_err());
+    assert!(!b.has_component::<Transform>());
+
+    a.add_component(Lifecycle::new(&log));
+    let lifecycle = a.take_component::<Lifecycle>().unwrap();
+    assert!(!a.has_component::<Lifecycle>());
+    assert!(a.take_component::<Lifecycle>().is_none());
+    b.add_component(lifecycle);
+    assert!(b.component::<Lifecycle>().unwrap().entity() == Some(b.clone()));
+    assert_eq!(*log.borrow(), vec!["attach", "enter", "exit", "detach", "attach", "enter"]);
+
+    a.take_component::<Transform>().unwrap();
+    assert!(!scene.has_component_manager::<TransformManager>());
+}
+#[test]
+fn test_component_replace_keeps_component_manager() {
+    let log: Log = Rc::new(RefCell::new(Vec::new()));
+    let mut scene = Scene::new();
+    let mut entity = Entity::new();
+    entity.add_component(Physics::new(&log));
+    scene.add_entity(entity.clone()).unwrap();
+    scene.init().unwrap();
+
+    assert!(entity.insert_component(Physics::new(&log)).unwrap().entity().is_none());
+    assert!(entity.replace_component(Physics::new(&log)).is_ok());
+    assert!(entity.component::<Physics>().unwrap().entity() == Some(entity.clone()));
+    assert_eq!(scene.with_component_manager::<PhysicsManager, _, _>(|physics_manager| physics_manager.components), Some(1));
+    assert_eq!(*log.borrow(), vec!["init PhysicsManager"]);
 }